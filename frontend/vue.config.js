@@ -0,0 +1,4 @@
+module.exports = {
+  // relative asset urls, so the app also works below the server base_path
+  publicPath: './',
+};
@@ -0,0 +1,385 @@
+use crate::config::{self, Config, MeiliSearchConfig, ProjectConfig, RootConfig, ServerConfig};
+
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use std::fmt::{self, Display};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use toml_edit::{ImDocument, Item, TableLike, Value};
+
+#[cfg(test)]
+#[path = "tests/check_tests.rs"]
+mod check_tests;
+
+// A problem found by check-config, with the line it was found at if known
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub file: Option<PathBuf>, // the included file of the problem, None for the config itself
+    pub line: Option<usize>,
+    pub message: String,
+    pub warning: bool, // worth a look, but the config works as it is
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}: ", line)?;
+        }
+        if self.warning {
+            write!(f, "warning: ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+// Check everything that would otherwise only fail at runtime, or not at all
+pub fn check_config(config_path: &str) -> Vec<Diagnostic> {
+    let content = match fs::read_to_string(config_path) {
+        Ok(content) => content,
+        Err(e) => {
+            return vec![Diagnostic {
+                file: None,
+                line: None,
+                message: format!("Failed to read {}: {}", config_path, e),
+                warning: false,
+            }]
+        }
+    };
+    let config_dir = Path::new(config_path).parent().unwrap_or(Path::new("."));
+    check_config_content(&content, config_dir)
+}
+
+// A parsed config file, the config itself or one of its included files
+struct Source {
+    file: Option<PathBuf>,
+    document: ImDocument<String>,
+}
+
+pub fn check_config_content(content: &str, config_dir: &Path) -> Vec<Diagnostic> {
+    // Without valid TOML nothing else can be checked
+    let document = match ImDocument::parse(content.to_string()) {
+        Ok(document) => document,
+        Err(e) => {
+            return vec![Diagnostic {
+                file: None,
+                line: e.span().map(|span| line_of(content, span)),
+                message: e.message().to_string(),
+                warning: false,
+            }]
+        }
+    };
+    let parsed = config::parse_config(content, config_dir);
+
+    // Projects of included files are checked in their own file, in the order they are appended
+    let mut sources = vec![Source { file: None, document }];
+    let mut project_sources: Vec<(usize, usize)> = (0..project_count(&sources[0].document))
+        .map(|i| (0, i))
+        .collect();
+    if let Ok(config) = &parsed {
+        for pattern in &config.include {
+            for path in config::include_paths(pattern, config_dir).unwrap_or_default() {
+                // unreadable files already failed parse_config
+                let Some(document) = fs::read_to_string(&path)
+                    .ok()
+                    .and_then(|content| ImDocument::parse(content).ok())
+                else {
+                    continue;
+                };
+                let source = sources.len();
+                project_sources.extend((0..project_count(&document)).map(|i| (source, i)));
+                sources.push(Source {
+                    file: Some(path),
+                    document,
+                });
+            }
+        }
+    }
+
+    let mut diagnostics = Vec::new();
+    let diagnostic = |(source, span): (usize, Option<Range<usize>>), message: String| {
+        let source: &Source = &sources[source];
+        Diagnostic {
+            file: source.file.clone(),
+            line: span.map(|span| line_of(source.document.raw(), span)),
+            message,
+            warning: false,
+        }
+    };
+    let mut report = |location, message| diagnostics.push(diagnostic(location, message));
+    // reported after the problems
+    let mut warnings = Vec::new();
+
+    // Unknown keys are ignored when loading, e.g. a misspelled option silently keeps its default
+    let root = sources[0].document.as_table();
+    let mut report_main = |span, message| report((0, span), message);
+    check_keys(root, known_keys::<Config>(), "the top level", &mut report_main);
+    if let Some(meilisearch) = root.get("meilisearch").and_then(|item| item.as_table_like()) {
+        check_keys(meilisearch, known_keys::<MeiliSearchConfig>(), "[meilisearch]", &mut report_main);
+    }
+    if let Some(server) = root.get("server").and_then(|item| item.as_table_like()) {
+        check_keys(server, known_keys::<ServerConfig>(), "[server]", &mut report_main);
+    }
+    if let Some(project_defaults) = root.get("project_defaults").and_then(|item| item.as_table_like()) {
+        check_keys(project_defaults, known_keys::<ProjectConfig>(), "[project_defaults]", &mut report_main);
+    }
+    for (i, source) in sources.iter().enumerate() {
+        let projects = source.document.as_table().get("projects");
+        for project in projects.and_then(|item| item.as_array_of_tables()).into_iter().flatten() {
+            let mut report_source = |span, message| report((i, span), message);
+            check_keys(project, known_keys::<ProjectConfig>(), "[[projects]]", &mut report_source);
+            for (root, _) in array_tables(project.get("roots")) {
+                check_keys(root, known_keys::<RootConfig>(), "roots", &mut report_source);
+            }
+        }
+    }
+
+    let mut config = match parsed {
+        Ok(config) => config,
+        Err(e) => {
+            report((0, e.span()), e.message().to_string());
+            return diagnostics;
+        }
+    };
+
+    // A project is located in the file it was written in
+    let locate = |key_path: &[&str]| match key_path {
+        ["projects", index, rest @ ..] => {
+            let Some(&(source, local_index)) =
+                index.parse::<usize>().ok().and_then(|index| project_sources.get(index))
+            else {
+                return (0, None);
+            };
+            let local_index = local_index.to_string();
+            let mut local_key_path = vec!["projects", local_index.as_str()];
+            local_key_path.extend(rest);
+            (source, locate(sources[source].document.as_table(), &local_key_path))
+        }
+        _ => (0, locate(root, key_path)),
+    };
+    if let Err(message) = config.apply_environment() {
+        report(locate(&["meilisearch", "meilisearch_api_key_file"]), message);
+    }
+    for problem in config.problems() {
+        let key_path: Vec<&str> = problem.key_path.iter().map(String::as_str).collect();
+        report(locate(&key_path), problem.message);
+    }
+
+    let meilisearch_config = &config.meilisearch;
+    if !meilisearch_config.meilisearch_bin_path.is_empty()
+        && meilisearch_config.meilisearch_api_key.len() < 16
+    {
+        report(
+            locate(&["meilisearch", "meilisearch_api_key"]),
+            "Meilisearch API key needs to be at least 16 bytes to start Meilisearch".to_string(),
+        );
+    }
+
+    // scans run without it, only the web server needs the built frontend
+    if !config.server.static_dir.is_dir() {
+        warnings.push((
+            locate(&["server", "static_dir"]),
+            format!(
+                "Static directory {:?} doesn't exist, build the frontend into it for the web server",
+                config.server.static_dir
+            ),
+        ));
+    }
+
+    for (i, project) in config.projects.iter().enumerate() {
+        let index = i.to_string();
+        let index = index.as_str();
+        // parsed the same way as by the scheduler
+        let job = tokio_cron_scheduler::Job::new_async(project.crontab.as_str(), |_, _| {
+            Box::pin(async {})
+        });
+        if job.is_err() {
+            report(
+                locate(&["projects", index, "crontab"]),
+                format!(
+                    "Invalid crontab {:?} of {}, expected \"SEC MIN HOUR DOM MON DOW\"",
+                    project.crontab, project.id
+                ),
+            );
+        }
+        // the root key comes first, then the roots array, as in ProjectConfig::roots
+        let root_keys = (!project.root.as_os_str().is_empty())
+            .then(|| vec!["root".to_string()])
+            .into_iter()
+            .chain((0..project.roots.len()).map(|j| vec!["roots".to_string(), j.to_string()]));
+        for (root, root_key) in project.roots().iter().zip(root_keys) {
+            let root_key: Vec<&str> = root_key.iter().map(String::as_str).collect();
+            let key_path = |key: &'static str| {
+                let mut key_path = vec!["projects", index];
+                key_path.extend(&root_key);
+                if root_key.len() > 1 {
+                    key_path.push(key);
+                }
+                key_path
+            };
+            if let Err(e) = fs::read_dir(&root.path) {
+                report(
+                    locate(&key_path("path")),
+                    format!("Root {:?} of {} can't be read: {}", root.path, project.id, e),
+                );
+                continue;
+            }
+            let (custom_ignore_rule_file, key_path) = match &root.custom_ignore_rule_file {
+                Some(file) => (Some(file), key_path("custom_ignore_rule_file")),
+                None => (
+                    project.custom_ignore_rule_file.as_ref(),
+                    vec!["projects", index, "custom_ignore_rule_file"],
+                ),
+            };
+            // the file is read in every folder that has one, the root may well have none
+            if let Some(custom_ignore_rule_file) = custom_ignore_rule_file {
+                if !root.path.join(custom_ignore_rule_file).is_file() {
+                    warnings.push((
+                        locate(&key_path),
+                        format!(
+                            "Ignore rule file {:?} of {} isn't in root {:?}, only folders below with one use it",
+                            custom_ignore_rule_file, project.id, root.path
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    diagnostics.extend(warnings.into_iter().map(|(location, message)| Diagnostic {
+        warning: true,
+        ..diagnostic(location, message)
+    }));
+    diagnostics
+}
+
+fn line_of(content: &str, span: Range<usize>) -> usize {
+    content[..span.start.min(content.len())].matches('\n').count() + 1
+}
+
+fn project_count(document: &ImDocument<String>) -> usize {
+    document
+        .get("projects")
+        .and_then(|item| item.as_array_of_tables())
+        .map_or(0, |projects| projects.len())
+}
+
+fn check_keys(
+    table: &dyn TableLike,
+    known_keys: &[&str],
+    section: &str,
+    report: &mut impl FnMut(Option<Range<usize>>, String),
+) {
+    for (key, _) in table.iter() {
+        if known_keys.contains(&key) {
+            continue;
+        }
+        let span = table.get_key_value(key).and_then(|(key, _)| key.span());
+        let suggestion = known_keys
+            .iter()
+            .map(|known_key| (edit_distance(key, known_key), known_key))
+            .filter(|(distance, _)| *distance <= 2)
+            .min();
+        let message = match suggestion {
+            Some((_, known_key)) => format!(
+                "Unknown key {:?} in {}, did you mean {:?}?",
+                key, section, known_key
+            ),
+            None => format!("Unknown key {:?} in {}", key, section),
+        };
+        report(span, message);
+    }
+}
+
+// The span of the deepest key of the path found in the document
+fn locate(root: &dyn TableLike, key_path: &[&str]) -> Option<Range<usize>> {
+    let mut table = root;
+    let mut span = None;
+    let mut keys = key_path.iter();
+    while let Some(key) = keys.next() {
+        let Some((key, item)) = table.get_key_value(key) else {
+            break;
+        };
+        span = key.span().or(span);
+        if item.is_array_of_tables() || item.is_array() {
+            let element = keys
+                .next()
+                .and_then(|index| index.parse::<usize>().ok())
+                .and_then(|index| array_tables(Some(item)).into_iter().nth(index));
+            let Some((element, element_span)) = element else {
+                break;
+            };
+            span = element_span.or(span);
+            table = element;
+        } else if let Some(inner_table) = item.as_table_like() {
+            table = inner_table;
+        } else {
+            break;
+        }
+    }
+    span
+}
+
+// The tables of an array of tables or of an array of inline tables, with their spans
+fn array_tables(item: Option<&Item>) -> Vec<(&dyn TableLike, Option<Range<usize>>)> {
+    match item {
+        Some(Item::ArrayOfTables(array)) => array
+            .iter()
+            .map(|table| (table as &dyn TableLike, table.span()))
+            .collect(),
+        Some(Item::Value(Value::Array(array))) => array
+            .iter()
+            .filter_map(|value| value.as_inline_table())
+            .map(|table| (table as &dyn TableLike, table.span()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut distances: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut previous = distances[0];
+        distances[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous + usize::from(a_char != *b_char);
+            previous = distances[j + 1];
+            distances[j + 1] = substitution.min(previous + 1).min(distances[j] + 1);
+        }
+    }
+    distances[b.len()]
+}
+
+// The keys of a config section, as serde derives them from the struct
+fn known_keys<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    let mut keys: &'static [&'static str] = &[];
+    let _ = T::deserialize(KeyCollector(&mut keys));
+    keys
+}
+
+// A deserializer that only records the field names of the struct it is asked for
+struct KeyCollector<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> Deserializer<'de> for KeyCollector<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(de::Error::custom("fields collected"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
@@ -0,0 +1,154 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+#[path = "tests/checkpoint_tests.rs"]
+mod checkpoint_tests;
+
+// Progress of an unfinished scan. The roots are walked in order and each walk is sorted
+// by file name, so every path before last_path in walk order has already been delivered
+// to Meilisearch, as have the roots before root.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScanCheckpoint {
+    pub root: PathBuf, // the root of last_path
+    #[serde(default)]
+    pub roots: Vec<PathBuf>, // all roots of the scan, empty for checkpoints of a single root
+    pub scan_generation: u64,
+    pub started_at: DateTime<Utc>,
+    pub last_path: Option<PathBuf>,
+    pub entry_count: usize,
+    pub bytes_scanned: u64,
+    #[serde(default)]
+    pub bytes_allocated: u64,
+    #[serde(default)]
+    pub hardlink_duplicate_count: usize,
+    // hardlink ids of the delivered files, so a resumed scan still counts each inode once.
+    // They can be many, so they are appended to a file next to the checkpoint instead,
+    // of which the first hardlinks_len bytes belong to it.
+    #[serde(skip)]
+    pub seen_hardlinks: HashSet<String>,
+    #[serde(skip)]
+    pub unsaved_hardlinks: Vec<String>,
+    #[serde(default)]
+    pub hardlinks_len: u64,
+    pub indexed_count_before: Option<usize>,
+}
+
+impl ScanCheckpoint {
+    pub fn scanned_roots(&self) -> Vec<PathBuf> {
+        if self.roots.is_empty() {
+            vec![self.root.clone()]
+        } else {
+            self.roots.clone()
+        }
+    }
+
+    // Whether the path and its whole subtree were walked before the checkpoint.
+    // Ancestors of last_path are walked again, as the rest of their subtree is not done yet.
+    pub fn is_done(&self, path: &Path) -> bool {
+        match &self.last_path {
+            Some(last_path) => path < last_path.as_path() && !last_path.starts_with(path),
+            None => false,
+        }
+    }
+
+    // Remember the hardlink id, returns whether it wasn't seen before
+    pub fn see_hardlink(&mut self, hardlink_id: &str) -> bool {
+        let is_new = self.seen_hardlinks.insert(hardlink_id.to_string());
+        if is_new {
+            self.unsaved_hardlinks.push(hardlink_id.to_string());
+        }
+        is_new
+    }
+
+    pub fn forget_hardlinks(&mut self) {
+        self.seen_hardlinks.clear();
+        self.unsaved_hardlinks.clear();
+        self.hardlinks_len = 0;
+    }
+
+    // Whether the path was delivered before the checkpoint. Unlike is_done this includes
+    // last_path and its ancestors, which are walked and sent again but were already counted.
+    pub fn was_delivered(&self, path: &Path) -> bool {
+        self.last_path
+            .as_deref()
+            .is_some_and(|last_path| path <= last_path)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScanCheckpoints {
+    pub dir: PathBuf,
+}
+
+impl ScanCheckpoints {
+    pub fn new(data_dir: &Path) -> Self {
+        ScanCheckpoints {
+            dir: data_dir.join("checkpoints"),
+        }
+    }
+
+    fn path(&self, project_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", project_id))
+    }
+
+    fn hardlinks_path(&self, project_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.hardlinks", project_id))
+    }
+
+    pub fn load(&self, project_id: &str) -> io::Result<Option<ScanCheckpoint>> {
+        let mut checkpoint: ScanCheckpoint = match fs::read_to_string(self.path(project_id)) {
+            Ok(content) => serde_json::from_str(&content)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if checkpoint.hardlinks_len > 0 {
+            let mut content = String::new();
+            File::open(self.hardlinks_path(project_id))?
+                .take(checkpoint.hardlinks_len)
+                .read_to_string(&mut content)?;
+            checkpoint.seen_hardlinks = content.lines().map(str::to_string).collect();
+        }
+        Ok(Some(checkpoint))
+    }
+
+    // Only the hardlink ids seen since the last save are written. Ids appended by a save
+    // that didn't get to write its checkpoint are cut off by the next one.
+    pub fn save(&self, project_id: &str, checkpoint: &mut ScanCheckpoint) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        if !checkpoint.unsaved_hardlinks.is_empty() {
+            let mut lines = String::new();
+            for hardlink_id in checkpoint.unsaved_hardlinks.drain(..) {
+                lines.push_str(&hardlink_id);
+                lines.push('\n');
+            }
+            let mut file = OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(self.hardlinks_path(project_id))?;
+            file.set_len(checkpoint.hardlinks_len)?;
+            file.seek(SeekFrom::End(0))?;
+            file.write_all(lines.as_bytes())?;
+            checkpoint.hardlinks_len += lines.len() as u64;
+        }
+        let path = self.path(project_id);
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_vec_pretty(checkpoint)?)?;
+        fs::rename(&temp_path, &path)
+    }
+
+    pub fn clear(&self, project_id: &str) -> io::Result<()> {
+        for path in [self.path(project_id), self.hardlinks_path(project_id)] {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,13 +1,27 @@
+use crate::presets;
+
+use ignore::overrides::{Override, OverrideBuilder};
 use serde::Deserialize;
+use std::env;
 use std::{
+    collections::HashSet,
     fmt::{self, Display},
-    path::PathBuf,
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
 };
 
+#[cfg(test)]
+#[path = "tests/config_tests.rs"]
+mod config_tests;
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct MeiliSearchConfig {
     pub meilisearch_url: String,
+    #[serde(default = "default_meilisearch_api_key")]
     pub meilisearch_api_key: String,
+    #[serde(default = "default_meilisearch_api_key_file")]
+    pub meilisearch_api_key_file: Option<PathBuf>, // read the API key from this file instead
     pub meilisearch_index_name: String,
     #[serde(default = "default_meilisearch_bin_path")]
     pub meilisearch_bin_path: String,
@@ -15,40 +29,231 @@ pub struct MeiliSearchConfig {
     pub meilisearch_db_path: String,
     #[serde(default = "default_meilisearch_telemetry")]
     pub meilisearch_telemetry: bool,
+    #[serde(default = "default_meilisearch_max_retries")]
+    pub meilisearch_max_retries: u32,
+    #[serde(default = "default_meilisearch_retry_base_delay_ms")]
+    pub meilisearch_retry_base_delay_ms: u64,
+    #[serde(default = "default_meilisearch_retry_max_delay_ms")]
+    pub meilisearch_retry_max_delay_ms: u64,
+    #[serde(default = "default_meilisearch_node_id")]
+    pub meilisearch_node_id: String, // scopes the document ids of this node, "default" by default
+    #[serde(default = "default_meilisearch_batch_max_bytes")]
+    pub meilisearch_batch_max_bytes: usize, // uncompressed NDJSON bytes of entries sent at once
+    #[serde(default = "default_meilisearch_gzip")]
+    pub meilisearch_gzip: bool, // compress the sent entries
 }
 
 impl Display for MeiliSearchConfig {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Meilisearch Configuration:")?;
         writeln!(f, "  URL: {}", self.meilisearch_url)?;
-        writeln!(f, "  API Key: **hidden**")?;
+        match &self.meilisearch_api_key_file {
+            Some(api_key_file) => writeln!(f, "  API Key: **hidden** (from {:?})", api_key_file)?,
+            None => writeln!(f, "  API Key: **hidden**")?,
+        }
         writeln!(f, "  Index Name: {}", self.meilisearch_index_name)?;
         writeln!(f, "  Binary Path: {}", self.meilisearch_bin_path)?;
         writeln!(f, "  Database Path: {}", self.meilisearch_db_path)?;
-        writeln!(f, "  Telemetry: {}", self.meilisearch_telemetry)
+        writeln!(f, "  Telemetry: {}", self.meilisearch_telemetry)?;
+        writeln!(
+            f,
+            "  Retries: {} (backoff {}ms to {}ms)",
+            self.meilisearch_max_retries,
+            self.meilisearch_retry_base_delay_ms,
+            self.meilisearch_retry_max_delay_ms
+        )?;
+        writeln!(
+            f,
+            "  Batches: up to {} bytes{}",
+            self.meilisearch_batch_max_bytes,
+            if self.meilisearch_gzip { ", gzipped" } else { "" }
+        )?;
+        writeln!(f, "  Node ID: {}", self.meilisearch_node_id)
     }
 }
 
 #[derive(Debug, Deserialize, Clone)]
+pub struct ServerConfig {
+    #[serde(default = "default_server_listen")]
+    pub listen: Vec<String>, // "0.0.0.0:3000", "[::]:3000" or "unix:/run/meili-finder.sock"
+    #[serde(default = "default_server_static_dir")]
+    pub static_dir: PathBuf,
+    #[serde(default = "default_server_base_path")]
+    pub base_path: String, // serve the app below this path, e.g. "/files"
+}
+
+// Where the server listens, parsed from ServerConfig.listen
+#[derive(Debug, Clone, PartialEq)]
+pub enum ListenAddress {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl ListenAddress {
+    pub fn parse(address: &str) -> Result<Self, String> {
+        if let Some(path) = address.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err(format!("Listen address {:?} has no socket path", address));
+            }
+            return Ok(ListenAddress::Unix(PathBuf::from(path)));
+        }
+        address
+            .parse()
+            .map(ListenAddress::Tcp)
+            .map_err(|_| format!("Invalid listen address {:?}, expected e.g. \"0.0.0.0:3000\", \"[::]:3000\" or \"unix:/path/to.sock\"", address))
+    }
+}
+
+impl Display for ListenAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListenAddress::Tcp(addr) => write!(f, "http://{}", addr),
+            ListenAddress::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+impl ServerConfig {
+    // The base path without trailing slash, empty when the app is served at the root
+    pub fn base_path(&self) -> &str {
+        self.base_path.trim_end_matches('/')
+    }
+}
+
+impl Display for ServerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Server Configuration:")?;
+        writeln!(f, "  Listen: {}", self.listen.join(", "))?;
+        writeln!(f, "  Static Directory: {:?}", self.static_dir)?;
+        writeln!(f, "  Base Path: {}/", self.base_path())
+    }
+}
+
+// One more directory scanned by a project, max_depth and the ignore file default to the project's
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct RootConfig {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    #[serde(default)]
+    pub custom_ignore_rule_file: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
 pub struct ProjectConfig {
     pub id: String,
-    pub root: PathBuf,
+    #[serde(default = "default_root")]
+    pub root: PathBuf, // may be left out when roots are given
+    #[serde(default = "default_roots")]
+    pub roots: Vec<RootConfig>, // scanned after root, in this order
     pub crontab: String,
     #[serde(default = "default_maxdepth")]
     pub max_depth: usize,
     #[serde(default = "default_custom_ignore_rule_file")]
     pub custom_ignore_rule_file: Option<String>,
+    #[serde(default = "default_respect_ignore_files")]
+    pub respect_gitignore: bool, // .gitignore and .git/info/exclude files
+    #[serde(default = "default_respect_ignore_files")]
+    pub respect_dot_ignore: bool, // .ignore files
+    #[serde(default = "default_respect_ignore_files")]
+    pub respect_git_global: bool, // the global excludes file of git, core.excludesFile
+    #[serde(default = "default_globs")]
+    pub include: Vec<String>, // gitignore-style globs, only files matching one are indexed
+    #[serde(default = "default_globs")]
+    pub exclude: Vec<String>, // gitignore-style globs of files and folders to skip
+    #[serde(default = "default_ignore_presets")]
+    pub ignore_presets: Vec<String>, // names of built-in exclude globs, e.g. synology or macos
+    #[serde(default = "default_exclude_if_present")]
+    pub exclude_if_present: Vec<String>, // folders containing one of these files are skipped
+    #[serde(default = "default_size_limit")]
+    pub min_size: Option<u64>, // in bytes, smaller files are skipped
+    #[serde(default = "default_size_limit")]
+    pub max_size: Option<u64>, // in bytes, larger files are skipped
+    #[serde(default = "default_modified_within")]
+    pub modified_within: Option<u64>, // in seconds, files modified earlier are skipped
     #[serde(default = "default_index_hidden")]
     pub index_hidden: bool,
     #[serde(default = "default_follow_symlinks")]
     pub follow_symlinks: bool,
+    #[serde(default = "default_same_file_system")]
+    pub same_file_system: bool, // don't descend into other filesystems mounted below a root
+    #[serde(default = "default_skip_pseudo_filesystems")]
+    pub skip_pseudo_filesystems: bool, // skip /proc, /sys, tmpfs, overlays... mounted below a root
+    #[serde(default = "default_max_scan_duration")]
+    pub max_scan_duration: Option<u64>, // in seconds, abort the scan once exceeded
+    #[serde(default = "default_max_entries_per_second")]
+    pub max_entries_per_second: Option<u32>,
+    #[serde(default = "default_pause_when_loadavg_above")]
+    pub pause_when_loadavg_above: Option<f64>, // the 1 minute load average of /proc/loadavg
+    #[serde(default = "default_idle_io_priority")]
+    pub idle_io_priority: bool, // scan with the idle IO scheduling class, Linux only
+    #[serde(default = "default_required_marker_file")]
+    pub required_marker_file: Option<String>, // relative to root, the scan aborts without it
+    #[serde(default = "default_max_delete_percentage")]
+    pub max_delete_percentage: Option<f64>, // refuse larger cleanups of the indexed entries
+    #[serde(default = "default_allow_mass_delete")]
+    pub allow_mass_delete: bool, // override max_delete_percentage
+    #[serde(default = "default_index_walk_errors")]
+    pub index_walk_errors: bool,
+    #[serde(default = "default_hook")]
+    pub pre_scan_command: Option<String>, // a failing command skips the scan
+    #[serde(default = "default_hook")]
+    pub post_scan_command: Option<String>,
+    #[serde(default = "default_hook")]
+    pub webhook_url: Option<String>,
+    #[serde(default = "default_skip_nested_projects")]
+    pub skip_nested_projects: bool, // leave roots of other projects inside root to those projects
+    #[serde(default = "default_previous_ids")]
+    pub previous_ids: Vec<String>, // documents left by these ids are moved to this project
+}
+
+impl ProjectConfig {
+    // Every root of the project, root first
+    pub fn roots(&self) -> Vec<RootConfig> {
+        let root = (!self.root.as_os_str().is_empty()).then(|| RootConfig {
+            path: self.root.clone(),
+            max_depth: None,
+            custom_ignore_rule_file: None,
+        });
+        root.into_iter().chain(self.roots.iter().cloned()).collect()
+    }
+
+    // The include and exclude globs and those of the ignore presets, matched relative to root.
+    // Matching globs take precedence over ignore rule files, like ripgrep's --glob.
+    pub fn overrides(&self, root: &Path) -> Result<Override, ignore::Error> {
+        let mut builder = OverrideBuilder::new(root);
+        for glob in &self.include {
+            builder.add(glob)?;
+        }
+        for glob in &self.exclude {
+            builder.add(&format!("!{}", glob))?;
+        }
+        // the last matching glob wins, so a preset can't be undone by an include glob
+        for preset in &self.ignore_presets {
+            for glob in presets::preset_globs(preset).unwrap_or_default() {
+                builder.add(&format!("!{}", glob))?;
+            }
+        }
+        builder.build()
+    }
 }
 
 impl Display for ProjectConfig {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Project Configuration:")?;
         writeln!(f, "  ID: {}", self.id)?;
-        writeln!(f, "  Root Directory: {:?}", self.root)?;
+        for root in self.roots() {
+            write!(f, "  Root Directory: {:?}", root.path)?;
+            match (root.max_depth, &root.custom_ignore_rule_file) {
+                (None, None) => writeln!(f)?,
+                (max_depth, ignore_file) => writeln!(
+                    f,
+                    " (max depth {}, ignore-rules file {})",
+                    max_depth.unwrap_or(self.max_depth),
+                    ignore_file.as_deref().unwrap_or("none")
+                )?,
+            }
+        }
         writeln!(f, "  Schedule: {}", self.crontab)?;
         writeln!(f, "  Max Depth(0 for infinitive depth): {}", self.max_depth)?;
         writeln!(
@@ -56,14 +261,96 @@ impl Display for ProjectConfig {
             "  Extra Ignore-rules File: {}",
             self.custom_ignore_rule_file.as_deref().unwrap_or("none")
         )?;
-        write!(
+        writeln!(
+            f,
+            "  Respect .gitignore: {}, .ignore: {}, Global Git Excludes: {}",
+            self.respect_gitignore, self.respect_dot_ignore, self.respect_git_global
+        )?;
+        if !self.include.is_empty() {
+            writeln!(f, "  Include: {}", self.include.join(", "))?;
+        }
+        if !self.exclude.is_empty() {
+            writeln!(f, "  Exclude: {}", self.exclude.join(", "))?;
+        }
+        if !self.ignore_presets.is_empty() {
+            writeln!(f, "  Ignore Presets: {}", self.ignore_presets.join(", "))?;
+        }
+        if !self.exclude_if_present.is_empty() {
+            writeln!(f, "  Exclude Folders Containing: {}", self.exclude_if_present.join(", "))?;
+        }
+        match (self.min_size, self.max_size) {
+            (None, None) => {}
+            (min_size, max_size) => writeln!(
+                f,
+                "  File Size: {} to {} bytes",
+                min_size.unwrap_or(0),
+                max_size.map_or("unlimited".to_string(), |max_size| max_size.to_string())
+            )?,
+        }
+        if let Some(seconds) = self.modified_within {
+            writeln!(f, "  Modified Within: {}s", seconds)?;
+        }
+        writeln!(
             f,
             "  Index Hidden File/Folders: {}\n  Follow Symlinks: {}",
             self.index_hidden, self.follow_symlinks
-        )
+        )?;
+        writeln!(
+            f,
+            "  Same File System: {}\n  Skip Pseudo Filesystems: {}",
+            self.same_file_system, self.skip_pseudo_filesystems
+        )?;
+        writeln!(
+            f,
+            "  Required Marker File: {}",
+            self.required_marker_file.as_deref().unwrap_or("none")
+        )?;
+        match self.max_delete_percentage {
+            Some(percentage) => writeln!(
+                f,
+                "  Max Delete Percentage: {}%{}",
+                percentage,
+                if self.allow_mass_delete { " (overridden)" } else { "" }
+            )?,
+            None => writeln!(f, "  Max Delete Percentage: unlimited")?,
+        }
+        writeln!(f, "  Index Walk Errors: {}", self.index_walk_errors)?;
+        writeln!(f, "  Skip Nested Projects: {}", self.skip_nested_projects)?;
+        if !self.previous_ids.is_empty() {
+            writeln!(f, "  Previous IDs: {}", self.previous_ids.join(", "))?;
+        }
+        match self.max_scan_duration {
+            Some(seconds) => writeln!(f, "  Max Scan Duration: {}s", seconds)?,
+            None => writeln!(f, "  Max Scan Duration: unlimited")?,
+        }
+        match self.max_entries_per_second {
+            Some(rate) => writeln!(f, "  Max Entries Per Second: {}", rate)?,
+            None => writeln!(f, "  Max Entries Per Second: unlimited")?,
+        }
+        if let Some(loadavg) = self.pause_when_loadavg_above {
+            writeln!(f, "  Pause When Load Average Above: {}", loadavg)?;
+        }
+        writeln!(f, "  Idle IO Priority: {}", self.idle_io_priority)?;
+        writeln!(
+            f,
+            "  Pre-scan Command: {}",
+            self.pre_scan_command.as_deref().unwrap_or("none")
+        )?;
+        writeln!(
+            f,
+            "  Post-scan Command: {}",
+            self.post_scan_command.as_deref().unwrap_or("none")
+        )?;
+        write!(f, "  Webhook: {}", self.webhook_url.as_deref().unwrap_or("none"))
     }
 }
 
+fn default_meilisearch_api_key() -> String {
+    "".to_string()
+}
+fn default_meilisearch_api_key_file() -> Option<PathBuf> {
+    None
+}
 fn default_meilisearch_bin_path() -> String {
     "".to_string()
 }
@@ -73,29 +360,176 @@ fn default_meilisearch_db_path() -> String {
 fn default_meilisearch_telemetry() -> bool {
     true
 }
+fn default_meilisearch_max_retries() -> u32 {
+    5
+}
+fn default_meilisearch_retry_base_delay_ms() -> u64 {
+    500
+}
+fn default_meilisearch_retry_max_delay_ms() -> u64 {
+    30000
+}
+fn default_meilisearch_batch_max_bytes() -> usize {
+    // well below the 100MB payload limit of Meilisearch by default
+    20 * 1024 * 1024
+}
+fn default_meilisearch_gzip() -> bool {
+    true
+}
+fn default_meilisearch_node_id() -> String {
+    // not the hostname, which changes with every recreated container and would give
+    // every document a new id. Nodes sharing Meilisearch already use their own index.
+    "default".to_string()
+}
+fn default_server_listen() -> Vec<String> {
+    vec!["0.0.0.0:3000".to_string()]
+}
+fn default_server_static_dir() -> PathBuf {
+    PathBuf::from("static")
+}
+fn default_server_base_path() -> String {
+    "/".to_string()
+}
+fn default_server() -> ServerConfig {
+    ServerConfig {
+        listen: default_server_listen(),
+        static_dir: default_server_static_dir(),
+        base_path: default_server_base_path(),
+    }
+}
+fn default_root() -> PathBuf {
+    PathBuf::new()
+}
+fn default_roots() -> Vec<RootConfig> {
+    Vec::new()
+}
 fn default_maxdepth() -> usize {
     0
 }
 fn default_custom_ignore_rule_file() -> Option<String> {
     None
 }
+fn default_respect_ignore_files() -> bool {
+    false
+}
+fn default_globs() -> Vec<String> {
+    Vec::new()
+}
+fn default_ignore_presets() -> Vec<String> {
+    Vec::new()
+}
+fn default_exclude_if_present() -> Vec<String> {
+    Vec::new()
+}
+fn default_size_limit() -> Option<u64> {
+    None
+}
+fn default_modified_within() -> Option<u64> {
+    None
+}
 fn default_index_hidden() -> bool {
     false
 }
 fn default_follow_symlinks() -> bool {
     false
 }
+fn default_same_file_system() -> bool {
+    false
+}
+fn default_skip_pseudo_filesystems() -> bool {
+    true
+}
+fn default_max_scan_duration() -> Option<u64> {
+    None
+}
+fn default_max_entries_per_second() -> Option<u32> {
+    None
+}
+fn default_pause_when_loadavg_above() -> Option<f64> {
+    None
+}
+fn default_idle_io_priority() -> bool {
+    false
+}
+fn default_required_marker_file() -> Option<String> {
+    None
+}
+// A volume that is mounted but half empty must not wipe the project
+fn default_max_delete_percentage() -> Option<f64> {
+    Some(50.0)
+}
+fn default_allow_mass_delete() -> bool {
+    false
+}
+fn default_index_walk_errors() -> bool {
+    false
+}
+fn default_hook() -> Option<String> {
+    None
+}
+fn default_skip_nested_projects() -> bool {
+    false
+}
+fn default_previous_ids() -> Vec<String> {
+    Vec::new()
+}
+
+fn default_data_dir() -> Option<PathBuf> {
+    None
+}
+fn default_include() -> Vec<String> {
+    Vec::new()
+}
+fn default_project_defaults() -> toml::Table {
+    toml::Table::new()
+}
+fn default_orphaned_projects() -> OrphanedProjects {
+    OrphanedProjects::Report
+}
+
+// What to do at startup with documents of project ids that are no longer configured.
+// Ids listed in previous_ids of a project are always migrated to that project.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OrphanedProjects {
+    Report,
+    Delete,
+}
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
     pub meilisearch: MeiliSearchConfig,
+    #[serde(default = "default_server")]
+    pub server: ServerConfig,
     pub projects: Vec<ProjectConfig>,
+    #[serde(default = "default_data_dir")]
+    pub data_dir: Option<PathBuf>, // local state, e.g. the outbox of undelivered batches
+    #[serde(default = "default_orphaned_projects")]
+    pub orphaned_projects: OrphanedProjects,
+    #[serde(default = "default_include")]
+    pub include: Vec<String>, // files with more [[projects]], relative to this file
+    #[serde(default = "default_project_defaults")]
+    pub project_defaults: toml::Table, // merged into every project, already applied
 }
 
 impl Display for Config {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let meilisearch_config = &self.meilisearch;
         writeln!(f, "{}", meilisearch_config)?;
+        writeln!(f, "{}", self.server)?;
+
+        match &self.data_dir {
+            Some(data_dir) => writeln!(f, "Data Directory: {:?}\n", data_dir)?,
+            None => writeln!(f, "Data Directory: none (outbox disabled)\n")?,
+        }
+        writeln!(f, "Orphaned Projects: {:?}\n", self.orphaned_projects)?;
+        if !self.include.is_empty() {
+            writeln!(f, "Included Project Files: {}\n", self.include.join(", "))?;
+        }
+        if !self.project_defaults.is_empty() {
+            let keys: Vec<&str> = self.project_defaults.keys().map(String::as_str).collect();
+            writeln!(f, "Project Defaults: {}\n", keys.join(", "))?;
+        }
 
         writeln!(f, "Projects:")?;
         for project in &self.projects {
@@ -109,7 +543,386 @@ impl Display for Config {
     }
 }
 
+// A problem that makes the config unusable, at the key it is about, e.g. ["projects", "1", "id"]
+#[derive(Debug, Clone)]
+pub struct ConfigProblem {
+    pub key_path: Vec<String>,
+    pub message: String,
+}
+
+impl ConfigProblem {
+    fn new(key_path: &[&str], message: String) -> Self {
+        ConfigProblem {
+            key_path: key_path.iter().map(|key| key.to_string()).collect(),
+            message,
+        }
+    }
+}
+
+impl Config {
+    // Apply the MEILI_FINDER_* environment overrides and read the API key file
+    pub fn apply_environment(&mut self) -> Result<(), String> {
+        let meilisearch_config = &mut self.meilisearch;
+        let string_overrides = [
+            ("meilisearch_url", &mut meilisearch_config.meilisearch_url),
+            ("meilisearch_api_key", &mut meilisearch_config.meilisearch_api_key),
+            ("meilisearch_index_name", &mut meilisearch_config.meilisearch_index_name),
+            ("meilisearch_bin_path", &mut meilisearch_config.meilisearch_bin_path),
+            ("meilisearch_db_path", &mut meilisearch_config.meilisearch_db_path),
+            ("meilisearch_node_id", &mut meilisearch_config.meilisearch_node_id),
+        ];
+        for (key, field) in string_overrides {
+            if let Ok(value) = env::var(env_override_name(key)) {
+                *field = value;
+            }
+        }
+        let path_overrides = [
+            ("meilisearch_api_key_file", &mut meilisearch_config.meilisearch_api_key_file),
+            ("data_dir", &mut self.data_dir),
+        ];
+        for (key, field) in path_overrides {
+            if let Ok(value) = env::var(env_override_name(key)) {
+                *field = Some(PathBuf::from(value));
+            }
+        }
+
+        // a key set by the environment wins over the key file
+        if env::var(env_override_name("meilisearch_api_key")).is_err() {
+            if let Some(api_key_file) = &meilisearch_config.meilisearch_api_key_file {
+                let api_key = std::fs::read_to_string(api_key_file).map_err(|e| {
+                    format!("Failed to read the API key file {:?}: {}", api_key_file, e)
+                })?;
+                meilisearch_config.meilisearch_api_key = api_key.trim().to_string();
+            }
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let problems = self.problems();
+        if problems.is_empty() {
+            return Ok(());
+        }
+        let messages: Vec<String> = problems.into_iter().map(|problem| problem.message).collect();
+        Err(messages.join("\n"))
+    }
+
+    // Every problem of the config itself, the environment is checked by check-config
+    pub fn problems(&self) -> Vec<ConfigProblem> {
+        let mut problems = Vec::new();
+        if self.meilisearch.meilisearch_api_key.is_empty() {
+            let message = format!(
+                "No Meilisearch API key, set meilisearch_api_key, meilisearch_api_key_file or {}",
+                env_override_name("meilisearch_api_key")
+            );
+            problems.push(ConfigProblem::new(&["meilisearch"], message));
+        }
+        if self.meilisearch.meilisearch_batch_max_bytes == 0 {
+            let message = "meilisearch_batch_max_bytes must be above 0".to_string();
+            problems.push(ConfigProblem::new(&["meilisearch", "meilisearch_batch_max_bytes"], message));
+        }
+        if self.server.listen.is_empty() {
+            let message = "The server has no listen address".to_string();
+            problems.push(ConfigProblem::new(&["server", "listen"], message));
+        }
+        for address in &self.server.listen {
+            if let Err(message) = ListenAddress::parse(address) {
+                problems.push(ConfigProblem::new(&["server", "listen"], message));
+            }
+        }
+        let base_path = &self.server.base_path;
+        if !base_path.starts_with('/') || base_path.contains(['{', '}', '*', '?', '#']) {
+            let message = format!("Base path {:?} must start with / and be a plain path", base_path);
+            problems.push(ConfigProblem::new(&["server", "base_path"], message));
+        }
+        let mut project_ids = HashSet::new();
+        for (i, project) in self.projects.iter().enumerate() {
+            let index = i.to_string();
+            let key_path = ["projects", index.as_str(), "id"];
+            if let Err(message) = validate_project_id(&project.id) {
+                problems.push(ConfigProblem::new(&key_path, message));
+            }
+            if !project_ids.insert(&project.id) {
+                let message = format!("Duplicate project id {:?}", project.id);
+                problems.push(ConfigProblem::new(&key_path, message));
+            }
+            if project.roots().is_empty() {
+                let message = format!("Project {} has no root, set root or roots", project.id);
+                problems.push(ConfigProblem::new(&key_path, message));
+            }
+            for (key, globs) in [("include", &project.include), ("exclude", &project.exclude)] {
+                for glob in globs {
+                    if let Err(e) = OverrideBuilder::new("").add(glob) {
+                        let message = format!("Invalid {} glob of {}: {}", key, project.id, e);
+                        problems.push(ConfigProblem::new(&["projects", index.as_str(), key], message));
+                    }
+                }
+            }
+            if project.max_entries_per_second == Some(0) {
+                let message = format!("max_entries_per_second of {} must be above 0", project.id);
+                let key_path = ["projects", index.as_str(), "max_entries_per_second"];
+                problems.push(ConfigProblem::new(&key_path, message));
+            }
+            if project.pause_when_loadavg_above.is_some_and(|loadavg| loadavg <= 0.0) {
+                let message = format!("pause_when_loadavg_above of {} must be above 0", project.id);
+                let key_path = ["projects", index.as_str(), "pause_when_loadavg_above"];
+                problems.push(ConfigProblem::new(&key_path, message));
+            }
+            for preset in &project.ignore_presets {
+                if presets::preset_globs(preset).is_none() {
+                    let message = format!(
+                        "Unknown ignore preset {:?} of {}, known presets are {}",
+                        preset,
+                        project.id,
+                        presets::preset_names().join(", ")
+                    );
+                    problems.push(ConfigProblem::new(&["projects", index.as_str(), "ignore_presets"], message));
+                }
+            }
+            if let (Some(min_size), Some(max_size)) = (project.min_size, project.max_size) {
+                if min_size > max_size {
+                    let message = format!(
+                        "min_size {} of {} is larger than its max_size {}",
+                        min_size, project.id, max_size
+                    );
+                    problems.push(ConfigProblem::new(&["projects", index.as_str(), "min_size"], message));
+                }
+            }
+        }
+        // a previous id belongs to exactly one project and is not in use anymore
+        let mut previous_ids = HashSet::new();
+        for (i, project) in self.projects.iter().enumerate() {
+            let index = i.to_string();
+            let key_path = ["projects", index.as_str(), "previous_ids"];
+            for previous_id in &project.previous_ids {
+                if let Err(message) = validate_project_id(previous_id) {
+                    problems.push(ConfigProblem::new(&key_path, message));
+                }
+                if project_ids.contains(previous_id) {
+                    let message = format!(
+                        "Previous id {:?} of {} is still used by a project",
+                        previous_id, project.id
+                    );
+                    problems.push(ConfigProblem::new(&key_path, message));
+                }
+                if !previous_ids.insert(previous_id) {
+                    let message = format!("Duplicate previous id {:?}", previous_id);
+                    problems.push(ConfigProblem::new(&key_path, message));
+                }
+            }
+        }
+        problems
+    }
+}
+
+// Project ids end up in Meilisearch filters and local state file names,
+// so only letters, digits, '-' and '_' are allowed
+pub fn validate_project_id(project_id: &str) -> Result<(), String> {
+    if project_id.is_empty() {
+        return Err("Project id must not be empty".to_string());
+    }
+    if let Some(c) = project_id
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && *c != '-' && *c != '_')
+    {
+        return Err(format!(
+            "Project id {:?} contains {:?}, only letters, digits, '-' and '_' are allowed",
+            project_id, c
+        ));
+    }
+    Ok(())
+}
+
+// Roots of the other projects inside a root of the given project.
+// Paths are compared as written, so write overlapping roots the same way.
+pub fn nested_roots(projects: &[ProjectConfig], project: &ProjectConfig) -> Vec<PathBuf> {
+    let project_roots = project.roots();
+    projects
+        .iter()
+        .filter(|other| other.id != project.id)
+        .flat_map(|other| other.roots())
+        .map(|other_root| other_root.path)
+        .filter(|other_root| {
+            project_roots
+                .iter()
+                .any(|root| *other_root != root.path && other_root.starts_with(&root.path))
+        })
+        .collect()
+}
+
+// The environment variable overriding a key, e.g. MEILI_FINDER_MEILISEARCH_URL
+pub fn env_override_name(key: &str) -> String {
+    format!("MEILI_FINDER_{}", key.to_uppercase())
+}
+
+// Expand ${VAR}, $VAR and a leading ~ of a string, $$ is a literal $.
+// Anything else after a $ is kept as it is, e.g. $(date +%s).
+pub fn expand_string(value: &str) -> Result<String, String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    if rest == "~" || rest.starts_with("~/") {
+        expanded.push_str(&env::var("HOME").map_err(|_| "HOME is not set to expand ~".to_string())?);
+        rest = &rest[1..];
+    }
+    while let Some(position) = rest.find('$') {
+        expanded.push_str(&rest[..position]);
+        rest = &rest[position + 1..];
+        let (name, remaining) = if let Some(braced) = rest.strip_prefix('{') {
+            let end = braced
+                .find('}')
+                .ok_or_else(|| format!("Unclosed ${{ in {:?}", value))?;
+            (&braced[..end], &braced[end + 1..])
+        } else if let Some(remaining) = rest.strip_prefix('$') {
+            expanded.push('$');
+            rest = remaining;
+            continue;
+        } else {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        };
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            expanded.push('$');
+            continue;
+        }
+        let variable = env::var(name)
+            .map_err(|_| format!("Environment variable {} in {:?} is not set", name, value))?;
+        expanded.push_str(&variable);
+        rest = remaining;
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+// Values taken as written: hook commands are left to the shell running them, which expands
+// variables itself, globs may name folders like $RECYCLE.BIN/ and an API key may contain a $
+fn is_literal(key: &str) -> bool {
+    key.ends_with("_command") || matches!(key, "include" | "exclude" | "meilisearch_api_key")
+}
+
+fn expand_value(key: &str, value: &mut toml::Value) -> Result<(), String> {
+    match value {
+        toml::Value::String(string) if !is_literal(key) => {
+            *string = expand_string(string)?;
+        }
+        toml::Value::Array(values) => {
+            for value in values {
+                expand_value(key, value)?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (key, value) in table.iter_mut() {
+                expand_value(key, value)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// The files matching an include pattern, sorted by name. Only the file name may
+// contain wildcards, e.g. "conf.d/*.toml", a pattern without any must match a file.
+pub fn include_paths(pattern: &str, config_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let pattern_path = config_dir.join(pattern);
+    let file_pattern = pattern_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| format!("Include {:?} doesn't name a file", pattern))?;
+    if !file_pattern.contains(['*', '?', '[', '{']) {
+        return Ok(vec![pattern_path]);
+    }
+    let matcher = globset::Glob::new(&file_pattern)
+        .map_err(|e| format!("Invalid include {:?}: {}", pattern, e))?
+        .compile_matcher();
+    let dir = pattern_path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read include directory {:?}: {}", dir, e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.file_name().is_some_and(|name| matcher.is_match(name)))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+// Append the [[projects]] of the included files, nothing else may be set there
+fn include_projects(config_table: &mut toml::Table, config_dir: &Path) -> Result<(), String> {
+    let patterns: Vec<String> = match config_table.get("include") {
+        Some(toml::Value::Array(patterns)) => patterns
+            .iter()
+            .filter_map(|pattern| pattern.as_str().map(str::to_string))
+            .collect(),
+        _ => return Ok(()),
+    };
+    for pattern in patterns {
+        for path in include_paths(&pattern, config_dir)? {
+            let content = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read included {:?}: {}", path, e))?;
+            let mut included: toml::Table =
+                toml::from_str(&content).map_err(|e| format!("In included {:?}: {}", path, e))?;
+            if let Some(key) = included.keys().find(|key| *key != "projects") {
+                return Err(format!("Included {:?} may only contain [[projects]], found {:?}", path, key));
+            }
+            let Some(toml::Value::Array(mut projects)) = included.remove("projects") else {
+                continue;
+            };
+            for project in &mut projects {
+                expand_value("", project)?;
+            }
+            match config_table
+                .entry("projects")
+                .or_insert_with(|| toml::Value::Array(Vec::new()))
+            {
+                toml::Value::Array(config_projects) => config_projects.append(&mut projects),
+                _ => return Err("projects must be an array of [[projects]]".to_string()),
+            }
+        }
+    }
+    Ok(())
+}
+
+// Fill the keys a project doesn't set from [project_defaults]
+fn apply_project_defaults(config_table: &mut toml::Table) {
+    let Some(toml::Value::Table(project_defaults)) = config_table.get("project_defaults").cloned() else {
+        return;
+    };
+    let Some(toml::Value::Array(projects)) = config_table.get_mut("projects") else {
+        return;
+    };
+    for project in projects.iter_mut().filter_map(toml::Value::as_table_mut) {
+        for (key, value) in &project_defaults {
+            project.entry(key.as_str()).or_insert_with(|| value.clone());
+        }
+    }
+}
+
+// Parse the config with its includes and project defaults, included files are relative to config_dir
+pub fn parse_config(config_content: &str, config_dir: &Path) -> Result<Config, toml::de::Error> {
+    let mut config_table: toml::Table = toml::from_str(config_content)?;
+    for (key, value) in config_table.iter_mut() {
+        // the include of the top level names config files, unlike the globs of a project
+        let key = if key == "include" { "" } else { key.as_str() };
+        expand_value(key, value).map_err(serde::de::Error::custom)?;
+    }
+    include_projects(&mut config_table, config_dir).map_err(serde::de::Error::custom)?;
+    apply_project_defaults(&mut config_table);
+    Config::deserialize(config_table).map_err(|e| {
+        // the same error of the config as written is reported with its position
+        match toml::from_str::<Config>(config_content) {
+            Err(spanned) if spanned.message() == e.message() => spanned,
+            _ => e,
+        }
+    })
+}
+
 pub fn read_config(config_path: &str) -> Result<Config, Box<dyn std::error::Error>> {
     let config_content = std::fs::read_to_string(config_path)?;
-    Ok(toml::from_str(&config_content)?)
+    let config_dir = Path::new(config_path).parent().unwrap_or(Path::new("."));
+    let mut config = parse_config(&config_content, config_dir)?;
+    config.apply_environment()?;
+    config.validate()?;
+    Ok(config)
 }
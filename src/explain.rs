@@ -0,0 +1,244 @@
+use crate::config::{ProjectConfig, RootConfig};
+use crate::presets;
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+#[path = "tests/explain_tests.rs"]
+mod explain_tests;
+
+// The rule that keeps a path out of the index
+#[derive(Debug, Clone, PartialEq)]
+pub enum IgnoreReason {
+    // a glob of the project's exclude option
+    ExcludeGlob(String),
+    // the project has include globs and none of them matches the file
+    NotIncluded,
+    // a glob of one of the project's ignore presets
+    Preset { preset: String, glob: String },
+    // the folder contains a file of exclude_if_present
+    MarkerFile(PathBuf),
+    // a pattern of an ignore rule file, e.g. a .gitignore
+    IgnoreFile { file: PathBuf, pattern: String },
+    // index_hidden is off
+    Hidden,
+}
+
+impl Display for IgnoreReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IgnoreReason::ExcludeGlob(glob) => write!(f, "exclude glob {:?}", glob),
+            IgnoreReason::NotIncluded => write!(f, "no include glob matches it"),
+            IgnoreReason::Preset { preset, glob } => {
+                write!(f, "glob {:?} of ignore preset {}", glob, preset)
+            }
+            IgnoreReason::MarkerFile(marker) => write!(f, "marker file {:?}", marker),
+            IgnoreReason::IgnoreFile { file, pattern } => {
+                write!(f, "pattern {:?} in {:?}", pattern, file)
+            }
+            IgnoreReason::Hidden => write!(f, "hidden entries are not indexed"),
+        }
+    }
+}
+
+// The ignored path, either the asked one or the folder of it that the walk never entered
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ignored {
+    pub path: PathBuf,
+    pub reason: IgnoreReason,
+}
+
+impl Display for Ignored {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} is ignored by {}", self.path, self.reason)
+    }
+}
+
+// Find which rule excludes a path from a project, following the precedence of the walk in
+// Indexer::walk_builder: ignore presets, include/exclude globs, then the nearest ignore rule
+// files, then hidden. Marker files of exclude_if_present are checked last.
+// Returns None if the path would be indexed as far as ignore rules are concerned.
+pub fn explain_ignored(project: &ProjectConfig, path: &Path) -> Result<Option<Ignored>, String> {
+    let root = project
+        .roots()
+        .into_iter()
+        .filter(|root| path.starts_with(&root.path))
+        .max_by_key(|root| root.path.components().count())
+        .ok_or_else(|| format!("{:?} is not inside a root of {}", path, project.id))?;
+    let globs = glob_matcher(project, &root.path)?;
+    let preset_globs = preset_matcher(project, &root.path)?;
+
+    // The walk never enters an ignored folder, so check the path from the top down
+    let relative = path.strip_prefix(&root.path).unwrap_or(path);
+    let mut current = root.path.clone();
+    for component in relative.components() {
+        current.push(component);
+        let is_dir = current.is_dir();
+        let reason = matched_preset(&preset_globs, &current, is_dir)
+            .or_else(|| matched_reason(project, &root, &globs, &current, is_dir))
+            .or_else(|| marker_file(project, &current, is_dir));
+        if let Some(reason) = reason {
+            return Ok(Some(Ignored {
+                path: current,
+                reason,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+// The include and exclude globs as a gitignore, whose patterns unlike those of an
+// ignore::overrides::Override can be reported
+fn glob_matcher(project: &ProjectConfig, root: &Path) -> Result<Gitignore, String> {
+    let mut builder = GitignoreBuilder::new(root);
+    for glob in &project.include {
+        builder.add_line(None, &format!("!{}", glob)).map_err(|e| e.to_string())?;
+    }
+    for glob in &project.exclude {
+        builder.add_line(None, glob).map_err(|e| e.to_string())?;
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+fn preset_matcher(project: &ProjectConfig, root: &Path) -> Result<Gitignore, String> {
+    let mut builder = GitignoreBuilder::new(root);
+    for preset in &project.ignore_presets {
+        for glob in presets::preset_globs(preset).unwrap_or_default() {
+            builder.add_line(None, glob).map_err(|e| e.to_string())?;
+        }
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+fn matched_preset(preset_globs: &Gitignore, path: &Path, is_dir: bool) -> Option<IgnoreReason> {
+    let Match::Ignore(glob) = preset_globs.matched(path, is_dir) else {
+        return None;
+    };
+    presets::IGNORE_PRESETS
+        .iter()
+        .find(|(_, globs)| globs.contains(&glob.original()))
+        .map(|(preset, _)| IgnoreReason::Preset {
+            preset: preset.to_string(),
+            glob: glob.original().to_string(),
+        })
+}
+
+fn marker_file(project: &ProjectConfig, path: &Path, is_dir: bool) -> Option<IgnoreReason> {
+    if !is_dir {
+        return None;
+    }
+    project
+        .exclude_if_present
+        .iter()
+        .map(|marker| path.join(marker))
+        .find(|marker| marker.exists())
+        .map(IgnoreReason::MarkerFile)
+}
+
+fn matched_reason(
+    project: &ProjectConfig,
+    root: &RootConfig,
+    globs: &Gitignore,
+    path: &Path,
+    is_dir: bool,
+) -> Option<IgnoreReason> {
+    // a whitelisted path skips the ignore files, but hidden entries stay out of the index
+    let whitelisted = match globs.matched(path, is_dir) {
+        Match::Ignore(glob) => return Some(IgnoreReason::ExcludeGlob(glob.original().to_string())),
+        Match::Whitelist(_) => true,
+        Match::None if !project.include.is_empty() && !is_dir => {
+            return Some(IgnoreReason::NotIncluded)
+        }
+        Match::None => false,
+    };
+
+    if !whitelisted {
+        if let Match::Ignore(reason) = ignore_files_matched(project, root, path, is_dir) {
+            return Some(reason);
+        }
+    }
+
+    let is_hidden = path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+    (is_hidden && !project.index_hidden).then_some(IgnoreReason::Hidden)
+}
+
+// Each kind of ignore file is matched by the nearest folder with a matching pattern,
+// the kinds take precedence in the order custom, .ignore, .gitignore, git exclude, global
+fn ignore_files_matched(
+    project: &ProjectConfig,
+    root: &RootConfig,
+    path: &Path,
+    is_dir: bool,
+) -> Match<IgnoreReason> {
+    let custom_ignore_rule_file = root
+        .custom_ignore_rule_file
+        .as_ref()
+        .or(project.custom_ignore_rule_file.as_ref());
+    // ignore files above the root are read along with the standard ones
+    let parents = project.respect_gitignore || project.respect_dot_ignore;
+
+    let mut custom_match = Match::None;
+    let mut dot_ignore_match = Match::None;
+    let mut gitignore_match = Match::None;
+    let mut git_exclude_match = Match::None;
+    let mut saw_git = false;
+    for dir in path.ancestors().skip(1) {
+        if !parents && !dir.starts_with(&root.path) {
+            break;
+        }
+        if let Some(file) = custom_ignore_rule_file.filter(|_| custom_match.is_none()) {
+            custom_match = file_matched(dir, &dir.join(file), path, is_dir);
+        }
+        if project.respect_dot_ignore && dot_ignore_match.is_none() {
+            dot_ignore_match = file_matched(dir, &dir.join(".ignore"), path, is_dir);
+        }
+        // git rules stop at the repository the path belongs to
+        if project.respect_gitignore && !saw_git {
+            if gitignore_match.is_none() {
+                gitignore_match = file_matched(dir, &dir.join(".gitignore"), path, is_dir);
+            }
+            if git_exclude_match.is_none() {
+                let exclude_file = dir.join(".git").join("info").join("exclude");
+                git_exclude_match = file_matched(dir, &exclude_file, path, is_dir);
+            }
+            saw_git = dir.join(".git").exists();
+        }
+    }
+    let global_match = if project.respect_git_global {
+        let (global, _) = Gitignore::global();
+        reason_of(global.matched(path, is_dir))
+    } else {
+        Match::None
+    };
+
+    custom_match
+        .or(dot_ignore_match)
+        .or(gitignore_match)
+        .or(git_exclude_match)
+        .or(global_match)
+}
+
+fn file_matched(dir: &Path, file: &Path, path: &Path, is_dir: bool) -> Match<IgnoreReason> {
+    if !file.is_file() {
+        return Match::None;
+    }
+    let mut builder = GitignoreBuilder::new(dir);
+    // like the walk, apply the valid lines of a partially invalid file
+    let _ = builder.add(file);
+    match builder.build() {
+        Ok(gitignore) => reason_of(gitignore.matched(path, is_dir)),
+        Err(_) => Match::None,
+    }
+}
+
+fn reason_of(matched: Match<&ignore::gitignore::Glob>) -> Match<IgnoreReason> {
+    matched.map(|glob| IgnoreReason::IgnoreFile {
+        file: glob.from().map(Path::to_path_buf).unwrap_or_default(),
+        pattern: glob.original().to_string(),
+    })
+}
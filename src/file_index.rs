@@ -1,14 +1,26 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 #[cfg(test)]
 #[path = "tests/file_index_tests.rs"]
 mod file_index_tests;
 
+// Document ids are derived from the path in a namespace of the node and the project,
+// so projects with overlapping roots never share a document. Documents without
+// an id_scheme were indexed with ids of the path alone, and are replaced on the next scan.
+pub const ID_SCHEME: u32 = 1;
+
+pub fn id_namespace(node_id: &str, project_id: &str) -> Uuid {
+    let namespace = format!("meili-finder://{}/{}", node_id, project_id);
+    Uuid::new_v5(&Uuid::NAMESPACE_URL, namespace.as_bytes())
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum IndexEntryType {
     File,
     Folder,
+    Error, // An entry that couldn't be read, the error is in the preview
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -18,10 +30,22 @@ pub struct FileSystemEntry {
     pub name: String,                         // The name of the file or folder
     pub entry_type: IndexEntryType,           // Whether it's a file or folder
     pub size: Option<u64>,                    // Size in bytes (None for folders)
+    #[serde(default)]
+    pub allocated_size: Option<u64>,          // Bytes allocated on disk, less for sparse or compressed files
     pub modified_date: Option<DateTime<Utc>>, // Last modified timestamp (optional for folders)
     pub is_hidden: bool,                      // Whether the entry is hidden
     pub preview: Option<String>,              // Optional preview content (for files only)
     pub project_id: String,                   // The project ID this entry belongs to
     pub entry_last_updated: i64,                    // The last timestamp this entry was updated
+    #[serde(default)]
+    pub scan_generation: u64,                 // The scan of the project that last saw this entry
+    #[serde(default)]
+    pub id_scheme: u32,                       // How the uuid was derived, see ID_SCHEME
+    #[serde(default)]
+    pub mount_point: Option<String>,          // The mount point of the filesystem holding the entry
+    #[serde(default)]
+    pub fs_type: Option<String>,              // The type of that filesystem, e.g. ext4 or nfs4
+    #[serde(default)]
+    pub hardlink_id: Option<String>,          // "device:inode" shared by the hardlinks of a file
 }
 
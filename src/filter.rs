@@ -0,0 +1,107 @@
+use std::fmt::{self, Display};
+
+#[cfg(test)]
+#[path = "tests/filter_tests.rs"]
+mod filter_tests;
+
+// A Meilisearch filter expression. Strings are always quoted and escaped,
+// so values like `my project` or `a OR b` can't change the meaning of the filter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Eq(&'static str, FilterValue),
+    Lt(&'static str, FilterValue),
+    NotExists(&'static str),
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterValue {
+    String(String),
+    Integer(i64),
+}
+
+impl From<&str> for FilterValue {
+    fn from(value: &str) -> Self {
+        FilterValue::String(value.to_string())
+    }
+}
+
+impl From<u64> for FilterValue {
+    fn from(value: u64) -> Self {
+        FilterValue::Integer(value as i64)
+    }
+}
+
+impl Filter {
+    pub fn eq(attribute: &'static str, value: impl Into<FilterValue>) -> Self {
+        Filter::Eq(attribute, value.into())
+    }
+
+    pub fn lt(attribute: &'static str, value: impl Into<FilterValue>) -> Self {
+        Filter::Lt(attribute, value.into())
+    }
+
+    pub fn not_exists(attribute: &'static str) -> Self {
+        Filter::NotExists(attribute)
+    }
+
+    pub fn and(self, other: Filter) -> Self {
+        match self {
+            Filter::And(mut filters) => {
+                filters.push(other);
+                Filter::And(filters)
+            }
+            filter => Filter::And(vec![filter, other]),
+        }
+    }
+
+    pub fn or(self, other: Filter) -> Self {
+        match self {
+            Filter::Or(mut filters) => {
+                filters.push(other);
+                Filter::Or(filters)
+            }
+            filter => Filter::Or(vec![filter, other]),
+        }
+    }
+}
+
+impl Display for FilterValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterValue::String(value) => {
+                write!(f, "\"")?;
+                for c in value.chars() {
+                    if c == '"' || c == '\\' {
+                        write!(f, "\\")?;
+                    }
+                    write!(f, "{}", c)?;
+                }
+                write!(f, "\"")
+            }
+            FilterValue::Integer(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |f: &mut fmt::Formatter<'_>, filters: &[Filter], operator: &str| {
+            for (i, filter) in filters.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " {} ", operator)?;
+                }
+                write!(f, "({})", filter)?;
+            }
+            Ok(())
+        };
+        match self {
+            Filter::Eq(attribute, value) => write!(f, "{} = {}", attribute, value),
+            Filter::Lt(attribute, value) => write!(f, "{} < {}", attribute, value),
+            Filter::NotExists(attribute) => write!(f, "{} NOT EXISTS", attribute),
+            Filter::And(filters) => join(f, filters, "AND"),
+            Filter::Or(filters) => join(f, filters, "OR"),
+        }
+    }
+}
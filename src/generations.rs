@@ -0,0 +1,42 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// The last scan generation of each project, so generations keep increasing
+// even if the index is lost or Meilisearch is unreachable when a scan starts
+#[derive(Debug, Clone)]
+pub struct ScanGenerations {
+    pub path: PathBuf,
+}
+
+impl ScanGenerations {
+    pub fn new(data_dir: &Path) -> Self {
+        ScanGenerations {
+            path: data_dir.join("generations.json"),
+        }
+    }
+
+    fn read_all(&self) -> io::Result<BTreeMap<String, u64>> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn last(&self, project_id: &str) -> io::Result<u64> {
+        Ok(self.read_all()?.get(project_id).copied().unwrap_or(0))
+    }
+
+    pub fn store(&self, project_id: &str, generation: u64) -> io::Result<()> {
+        let mut generations = self.read_all()?;
+        generations.insert(project_id.to_string(), generation);
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp_path = self.path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_vec_pretty(&generations)?)?;
+        fs::rename(&temp_path, &self.path)
+    }
+}
@@ -0,0 +1,53 @@
+use crate::indexer::ScanSummary;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+#[path = "tests/history_tests.rs"]
+mod history_tests;
+
+// Scan history, one JSON summary per line in the order the scans finished
+#[derive(Debug, Clone)]
+pub struct ScanHistory {
+    pub path: PathBuf,
+}
+
+impl ScanHistory {
+    pub fn new(data_dir: &Path) -> Self {
+        ScanHistory {
+            path: data_dir.join("history.ndjson"),
+        }
+    }
+
+    pub fn record(&self, summary: &ScanSummary) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut line = serde_json::to_vec(summary)?;
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(&line)
+    }
+
+    // Return the latest scans first, optionally only those of one project
+    pub fn read(&self, project_id: Option<&str>, limit: usize) -> io::Result<Vec<ScanSummary>> {
+        let history = match File::open(&self.path) {
+            Ok(history) => history,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut summaries: Vec<ScanSummary> = BufReader::new(history)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str::<ScanSummary>(&line).ok())
+            .filter(|summary| project_id.is_none_or(|id| summary.project_id == id))
+            .collect();
+        summaries.reverse();
+        summaries.truncate(limit);
+        Ok(summaries)
+    }
+}
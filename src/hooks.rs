@@ -0,0 +1,79 @@
+use crate::indexer::ScanSummary;
+
+use axum::body::Body;
+use hyper::{header::CONTENT_TYPE, Request};
+use hyper_util::{client::legacy::connect::HttpConnector, rt::TokioExecutor};
+use serde::Serialize;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+#[cfg(test)]
+#[path = "tests/hooks_tests.rs"]
+mod hooks_tests;
+
+const COMMAND_HOOK_TIMEOUT: Duration = Duration::from_secs(600);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The JSON passed to the hooks, the summary is empty for pre_scan
+#[derive(Serialize)]
+struct HookPayload<'a> {
+    event: &'a str,
+    #[serde(flatten)]
+    summary: &'a ScanSummary,
+}
+
+fn payload(event: &str, summary: &ScanSummary) -> String {
+    serde_json::to_string(&HookPayload { event, summary }).unwrap_or_default()
+}
+
+// Run the command with `sh -c`, the JSON payload is written to its stdin
+pub async fn run_command_hook(
+    event: &str,
+    command: &str,
+    summary: &ScanSummary,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("MEILI_FINDER_HOOK_EVENT", event)
+        .env("MEILI_FINDER_PROJECT_ID", &summary.project_id)
+        .stdin(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // the command may not read its stdin at all, that's fine
+        let _ = stdin.write_all(payload(event, summary).as_bytes()).await;
+    }
+
+    let status = tokio::time::timeout(COMMAND_HOOK_TIMEOUT, child.wait())
+        .await
+        .map_err(|_| format!("{} hook timed out", event))??;
+    if !status.success() {
+        return Err(format!("{} hook exited with {}", event, status).into());
+    }
+    Ok(())
+}
+
+// POST the JSON payload to the webhook, only plain http is supported
+pub async fn post_webhook(
+    event: &str,
+    webhook_url: &str,
+    summary: &ScanSummary,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = hyper_util::client::legacy::Client::builder(TokioExecutor::new())
+        .build::<_, Body>(HttpConnector::new());
+    let request = Request::post(webhook_url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(payload(event, summary)))?;
+
+    let response = tokio::time::timeout(WEBHOOK_TIMEOUT, client.request(request))
+        .await
+        .map_err(|_| "webhook timed out")??;
+    if !response.status().is_success() {
+        return Err(format!("webhook responded with {}", response.status()).into());
+    }
+    Ok(())
+}
@@ -1,12 +1,29 @@
-use crate::config::{MeiliSearchConfig, ProjectConfig};
-use crate::file_index::{FileSystemEntry, IndexEntryType};
+use crate::checkpoint::{ScanCheckpoint, ScanCheckpoints};
+use crate::config::{MeiliSearchConfig, ProjectConfig, RootConfig};
+use crate::file_index::{self, FileSystemEntry, IndexEntryType, ID_SCHEME};
+use crate::filter::Filter;
+use crate::generations::ScanGenerations;
+use crate::mounts::{MountTable, RootMounts};
+use crate::ndjson::NdjsonBatch;
+use crate::outbox::{Outbox, OutboxBatch};
+use crate::retry::RetryPolicy;
+use crate::throttle::{IdleIoPriority, Throttle};
 use chrono::{DateTime, Utc};
+use bytes::Bytes;
 use ignore::WalkBuilder;
-use meilisearch_sdk::documents::DocumentDeletionQuery;
+use meilisearch_sdk::documents::{DocumentDeletionQuery, DocumentsQuery};
 use meilisearch_sdk::indexes::IndexesQuery;
-use std::collections::HashSet;
+use meilisearch_sdk::search::SearchQuery;
+use meilisearch_sdk::task_info::TaskInfo;
+use meilisearch_sdk::tasks::TasksSearchQuery;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
 #[cfg(test)]
@@ -18,11 +35,246 @@ pub struct Indexer {
     pub project_config: ProjectConfig,
     pub meili_index_name: String,
     pub meili_client: Option<meilisearch_sdk::client::Client>,
+    pub retry_policy: RetryPolicy,
+    pub outbox: Option<Outbox>,
+    pub generations: Option<ScanGenerations>,
+    pub checkpoints: Option<ScanCheckpoints>,
+    pub id_namespace: Uuid,
+    pub nested_roots: Vec<PathBuf>, // roots of other projects, skipped with skip_nested_projects
+    pub batch_max_bytes: usize,      // a batch is sent before its NDJSON payload exceeds this
+    pub gzip_batches: bool,
+    http_client: reqwest::Client, // sends the batches, the SDK can't compress them
 }
 
-// suppose each entry take 2kb, 10000 entries will take 20MB,
-// which is lower than the default meilisearch batch limit of 100MB
-const MEILISEARCH_BATCH_ENTRIES_LIMIT: usize = 10000;
+// the walk itself is blocking, so give other tasks (server, signal handler)
+// a chance to run every now and then on the current-thread runtime
+const YIELD_EVERY_ENTRIES: usize = 1024;
+
+// only the first walk errors are kept in detail, the rest is only counted by kind
+const MAX_REPORTED_WALK_ERRORS: usize = 100;
+
+// how to wait for the last batch to be processed before cleaning up
+pub const TASK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+pub const TASK_TIMEOUT: Duration = Duration::from_secs(3600);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanStatus {
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+// Summary of a scan, reported to the scheduler, the hooks and the scan history
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScanSummary {
+    pub project_id: String,
+    pub status: ScanStatus,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub entry_count: usize,
+    pub bytes_scanned: u64,     // total size of the scanned files, hardlinks counted once
+    #[serde(default)]
+    pub bytes_allocated: u64, // total size on disk of the scanned files, hardlinks counted once
+    #[serde(default)]
+    pub hardlink_duplicate_count: usize, // files whose inode was already counted via another link
+    pub walk_error_count: usize, // entries that couldn't be read during the walk
+    #[serde(default)]
+    pub walk_error_kinds: BTreeMap<String, usize>,
+    #[serde(default)]
+    pub walk_errors: Vec<WalkError>, // only the first MAX_REPORTED_WALK_ERRORS
+    pub task_failure_count: Option<usize>, // Meilisearch tasks of this scan that failed, None if unknown
+    pub added_count: Option<usize>, // None if Meilisearch couldn't tell
+    pub removed_count: Option<usize>,
+    #[serde(
+        rename = "duration_secs",
+        serialize_with = "serialize_duration_secs",
+        deserialize_with = "deserialize_duration_secs"
+    )]
+    pub duration: Duration,
+    pub errors: Vec<String>,
+    #[serde(default)]
+    pub scan_generation: u64,
+    #[serde(default)]
+    pub resumed: bool, // continued from the checkpoint of an interrupted scan
+}
+
+impl ScanSummary {
+    pub fn new(project_id: &str) -> Self {
+        let time_now = Utc::now();
+        ScanSummary {
+            project_id: project_id.to_string(),
+            status: ScanStatus::Succeeded,
+            started_at: time_now,
+            finished_at: time_now,
+            entry_count: 0,
+            bytes_scanned: 0,
+            bytes_allocated: 0,
+            hardlink_duplicate_count: 0,
+            walk_error_count: 0,
+            walk_error_kinds: BTreeMap::new(),
+            walk_errors: Vec::new(),
+            task_failure_count: Some(0),
+            added_count: None,
+            removed_count: None,
+            duration: Duration::ZERO,
+            errors: Vec::new(),
+            scan_generation: 0,
+            resumed: false,
+        }
+    }
+
+    pub fn record_walk_error(&mut self, walk_error: WalkError) {
+        self.walk_error_count += 1;
+        *self.walk_error_kinds.entry(walk_error.kind.clone()).or_default() += 1;
+        if self.walk_errors.len() < MAX_REPORTED_WALK_ERRORS {
+            self.walk_errors.push(walk_error);
+        }
+    }
+}
+
+// An entry that couldn't be read during the walk, e.g. a permission-denied directory
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WalkError {
+    pub path: Option<String>,
+    pub kind: String, // std::io::ErrorKind, or "FilesystemLoop" / "Other"
+    pub message: String,
+}
+
+impl WalkError {
+    fn from_io_error(path: &Path, e: &io::Error) -> Self {
+        WalkError {
+            path: Some(path.to_string_lossy().to_string()),
+            kind: format!("{:?}", e.kind()),
+            message: e.to_string(),
+        }
+    }
+
+    fn from_ignore_error(e: &ignore::Error) -> Self {
+        let kind = match e.io_error() {
+            Some(io_error) => format!("{:?}", io_error.kind()),
+            None if e.is_io() => "Other".to_string(),
+            None => match ignore_error_root(e) {
+                ignore::Error::Loop { .. } => "FilesystemLoop".to_string(),
+                _ => "Other".to_string(),
+            },
+        };
+        WalkError {
+            path: ignore_error_path(e).map(|path| path.to_string_lossy().to_string()),
+            kind,
+            message: e.to_string(),
+        }
+    }
+}
+
+fn ignore_error_root(e: &ignore::Error) -> &ignore::Error {
+    match e {
+        ignore::Error::WithPath { err, .. }
+        | ignore::Error::WithDepth { err, .. }
+        | ignore::Error::WithLineNumber { err, .. } => ignore_error_root(err),
+        _ => e,
+    }
+}
+
+fn ignore_error_path(e: &ignore::Error) -> Option<&Path> {
+    match e {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            ignore_error_path(err)
+        }
+        ignore::Error::Loop { child, .. } => Some(child),
+        _ => None,
+    }
+}
+
+fn serialize_duration_secs<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+fn deserialize_duration_secs<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    let seconds = f64::deserialize(deserializer)?;
+    Duration::try_from_secs_f64(seconds).map_err(serde::de::Error::custom)
+}
+
+#[derive(Debug)]
+pub enum IndexError {
+    // the scan was aborted via API or shutdown signal
+    Cancelled,
+    // the scan took longer than the project's max_scan_duration
+    TimeLimitExceeded(Duration),
+    Meilisearch(meilisearch_sdk::errors::Error),
+    // failed to spool a batch to the outbox
+    Outbox(std::io::Error),
+    // failed to read or write local state, e.g. the scan generations
+    State(std::io::Error),
+    // the root doesn't exist, e.g. the volume is not mounted
+    RootMissing(PathBuf),
+    // the required marker file under the root doesn't exist
+    MarkerMissing(PathBuf),
+    // an include or exclude glob of the project is invalid
+    InvalidGlob(ignore::Error),
+    // batches of the scan failed in Meilisearch, None if that couldn't be checked
+    TasksFailed(Option<usize>),
+    // the cleanup would delete more than max_delete_percentage of the indexed entries
+    TooManyDeletions {
+        obselete_count: usize,
+        indexed_count: usize,
+        max_percentage: f64,
+    },
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexError::Cancelled => write!(f, "scan was cancelled"),
+            IndexError::TimeLimitExceeded(limit) => {
+                write!(f, "scan exceeded the time limit of {}s", limit.as_secs())
+            }
+            IndexError::Meilisearch(e) => write!(f, "meilisearch error: {}", e),
+            IndexError::Outbox(e) => write!(f, "outbox error: {}", e),
+            IndexError::State(e) => write!(f, "local state error: {}", e),
+            IndexError::RootMissing(root) => {
+                write!(f, "root {:?} doesn't exist or is not a directory", root)
+            }
+            IndexError::MarkerMissing(marker) => {
+                write!(f, "marker file {:?} doesn't exist, is the volume mounted?", marker)
+            }
+            IndexError::InvalidGlob(e) => write!(f, "invalid include or exclude glob: {}", e),
+            IndexError::TasksFailed(Some(failure_count)) => write!(
+                f,
+                "{} batches failed in Meilisearch, obselete entries are kept",
+                failure_count
+            ),
+            IndexError::TasksFailed(None) => write!(
+                f,
+                "the sent batches couldn't be verified, obselete entries are kept"
+            ),
+            IndexError::TooManyDeletions {
+                obselete_count,
+                indexed_count,
+                max_percentage,
+            } => write!(
+                f,
+                "refused to delete {} of {} indexed entries (more than {}%), \
+                 set allow_mass_delete to override",
+                obselete_count, indexed_count, max_percentage
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IndexError {}
+
+impl From<meilisearch_sdk::errors::Error> for IndexError {
+    fn from(e: meilisearch_sdk::errors::Error) -> Self {
+        IndexError::Meilisearch(e)
+    }
+}
 
 impl Indexer {
     // Create a new Indexer instance
@@ -40,22 +292,32 @@ impl Indexer {
             project_config: project_config.clone(),
             meili_index_name: meilisearch_config.meilisearch_index_name.clone(),
             meili_client,
+            retry_policy: RetryPolicy::from_config(meilisearch_config),
+            outbox: None,
+            generations: None,
+            checkpoints: None,
+            id_namespace: file_index::id_namespace(
+                &meilisearch_config.meilisearch_node_id,
+                &project_config.id,
+            ),
+            nested_roots: Vec::new(),
+            batch_max_bytes: meilisearch_config.meilisearch_batch_max_bytes,
+            gzip_batches: meilisearch_config.meilisearch_gzip,
+            http_client: reqwest::Client::new(),
         }
     }
 
-    pub async fn configure_meilisearch_index(&self) {
+    pub async fn configure_meilisearch_index(&self) -> Result<(), IndexError> {
         let index_name = &self.meili_index_name;
+        let retry_policy = &self.retry_policy;
         if let Some(unwrapped_meili_client) = &self.meili_client {
             // List all indexes
-            let indexes_query = IndexesQuery::new(&unwrapped_meili_client)
-                .with_limit(1024)
-                .execute()
-                .await;
-            if indexes_query.is_err() {
-                eprintln!("Failed to list indexs!");
-            }
-            let meili_indexes: HashSet<String> = indexes_query
-                .unwrap()
+            let mut indexes_query = IndexesQuery::new(unwrapped_meili_client);
+            indexes_query.with_limit(1024);
+            let meili_indexes: HashSet<String> = retry_policy
+                .retry("Listing indexes", || indexes_query.execute())
+                .await
+                .inspect_err(|_| eprintln!("Failed to list indexs!"))?
                 .results
                 .into_iter()
                 .map(|index| index.uid)
@@ -63,23 +325,24 @@ impl Indexer {
 
             // Create index if not existing
             if !meili_indexes.contains(index_name) {
-                let index_creation = unwrapped_meili_client
-                    .create_index(index_name, Some("uuid"))
-                    .await;
-                if index_creation.is_err() {
-                    eprintln!("Failed to create index {}!", index_name);
-                }
+                retry_policy
+                    .retry("Creating index", || {
+                        unwrapped_meili_client.create_index(index_name, Some("uuid"))
+                    })
+                    .await
+                    .inspect_err(|_| eprintln!("Failed to create index {}!", index_name))?;
             }
 
+            let meili_index = unwrapped_meili_client.index(index_name);
+
             // Update filterable attributes
-            let existing_filterable_attributes: HashSet<String> = match unwrapped_meili_client
-                .index(index_name)
-                .get_filterable_attributes()
+            let existing_filterable_attributes: HashSet<String> = retry_policy
+                .retry("Getting filterable attributes", || {
+                    meili_index.get_filterable_attributes()
+                })
                 .await
-            {
-                Ok(filterable_attributes) => filterable_attributes.into_iter().collect(),
-                Err(_) => HashSet::new(),
-            };
+                .map(|filterable_attributes| filterable_attributes.into_iter().collect())
+                .unwrap_or_default();
             let filterable_attributes = [
                 "path",
                 "name",
@@ -90,150 +353,723 @@ impl Indexer {
                 "preview",
                 "project_id",
                 "entry_last_updated",
+                "scan_generation",
+                "id_scheme",
+                "mount_point",
+                "fs_type",
+                "allocated_size",
+                "hardlink_id",
             ];
             if filterable_attributes
                 .iter()
                 .any(|attr| !existing_filterable_attributes.contains(*attr))
             {
-                let filterable_attributes = unwrapped_meili_client
-                    .index(index_name)
-                    .set_filterable_attributes(&filterable_attributes)
-                    .await;
-                if filterable_attributes.is_err() {
-                    eprintln!("Failed to update filterable attributes!");
-                }
+                retry_policy
+                    .retry("Updating filterable attributes", || {
+                        meili_index.set_filterable_attributes(&filterable_attributes)
+                    })
+                    .await
+                    .inspect_err(|_| eprintln!("Failed to update filterable attributes!"))?;
             }
 
             // Update sortable attributes
-            let existing_sortable_attibutes: HashSet<String> = match unwrapped_meili_client
-                .index(index_name)
-                .get_sortable_attributes()
+            let existing_sortable_attibutes: HashSet<String> = retry_policy
+                .retry("Getting sortable attributes", || {
+                    meili_index.get_sortable_attributes()
+                })
                 .await
-            {
-                Ok(sortable_attributes) => sortable_attributes.into_iter().collect(),
-                Err(_) => HashSet::new(),
-            };
-            let sortable_attributes = ["path", "name", "size", "modified_date"];
+                .map(|sortable_attributes| sortable_attributes.into_iter().collect())
+                .unwrap_or_default();
+            let sortable_attributes = [
+                "path",
+                "name",
+                "size",
+                "allocated_size",
+                "modified_date",
+                "scan_generation",
+            ];
             if sortable_attributes
                 .iter()
                 .any(|attr| !existing_sortable_attibutes.contains(*attr))
             {
-                let sortable_attributes = unwrapped_meili_client
-                    .index(index_name)
-                    .set_sortable_attributes(&sortable_attributes)
-                    .await;
-                if sortable_attributes.is_err() {
-                    eprintln!("Failed to update sortable attributes!");
-                }
+                retry_policy
+                    .retry("Updating sortable attributes", || {
+                        meili_index.set_sortable_attributes(&sortable_attributes)
+                    })
+                    .await
+                    .inspect_err(|_| eprintln!("Failed to update sortable attributes!"))?;
             }
 
             // Update separators to preserve symbol and numbers in the path, etc.
-            let seperators_to_remove = [
+            let seperators_to_remove: Vec<String> = [
                 // ".", "/", "\\", "@", "#", "$", "%", "^", "&", "*", "(", ")", "-", "_", "+", "=",
                 "-", "_",
-            ];
-            let update_seperators = unwrapped_meili_client
-                .index(index_name)
-                // .set_dictionary(["@", sep])
-                .set_non_separator_tokens(&seperators_to_remove.into_iter().map(|s| s.to_string()).collect())
-                .await;
-            if update_seperators.is_err() {
-                eprintln!("Failed to update separators!");
-            }
+            ]
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect();
+            retry_policy
+                .retry("Updating separators", || {
+                    // .set_dictionary(["@", sep])
+                    meili_index.set_non_separator_tokens(&seperators_to_remove)
+                })
+                .await
+                .inspect_err(|_| eprintln!("Failed to update separators!"))?;
         }
+        Ok(())
     }
 
+    // A failed or aborted scan still returns its summary, with what it got through
     pub async fn index_files(
         &self,
-    ) -> Result<(Vec<FileSystemEntry>, usize), meilisearch_sdk::errors::Error> {
+        cancel_token: &CancellationToken,
+    ) -> Result<(Vec<FileSystemEntry>, ScanSummary), (ScanSummary, IndexError)> {
+        let mut summary = ScanSummary::new(&self.project_config.id);
+        match self.scan_roots(cancel_token, &mut summary).await {
+            Ok(scanned_entries) => Ok((scanned_entries, summary)),
+            Err(e) => Err((summary, e)),
+        }
+    }
+
+    async fn scan_roots(
+        &self,
+        cancel_token: &CancellationToken,
+        summary: &mut ScanSummary,
+    ) -> Result<Vec<FileSystemEntry>, IndexError> {
         let mut scanned_entries = Vec::new();
         // scan and index files and folders
-        // return the last batch of scanned entries and the total size of all scanned entries
+        // return the last batch of scanned entries, the summary covers the whole scan
+        // the scan can be aborted between entries by cancel_token or max_scan_duration,
+        // an aborted scan never cleans obselete index since not every entry was visited
 
-        // Recursively scan the directory
-        // Use WalkBuilder to apply ignore rules efficiently
-        // TODO: maybe record the uuid with modification time and skip ones same as the last-time scan
-        let mut walkerbuilder = WalkBuilder::new(&self.project_config.root);
-        walkerbuilder
-            .standard_filters(false)
-            .hidden(!self.project_config.index_hidden)
-            .follow_links(self.project_config.follow_symlinks);
+        // Never scan an unmounted volume, the cleanup would wipe the whole project
+        let roots = self.project_config.roots();
+        for root in &roots {
+            if !root.path.is_dir() {
+                return Err(IndexError::RootMissing(root.path.clone()));
+            }
+            if let Some(marker_file) = &self.project_config.required_marker_file {
+                let marker_path = root.path.join(marker_file);
+                if !marker_path.exists() {
+                    return Err(IndexError::MarkerMissing(marker_path));
+                }
+            }
+        }
 
-        if self.project_config.max_depth > 0 {
-            walkerbuilder.max_depth(Some(self.project_config.max_depth));
+        // Overlapping projects each index the shared subtree with their own document ids,
+        // unless the outer project leaves it to the nested ones
+        let skipped_roots = if self.project_config.skip_nested_projects {
+            self.nested_roots.clone()
         } else {
-            walkerbuilder.max_depth(None);
-        }
+            Vec::new()
+        };
+        // Read once per scan, only Linux has /proc/self/mountinfo
+        let mounts = MountTable::read().unwrap_or_default();
+        let root_mounts: Vec<RootMounts> = roots.iter().map(|root| mounts.for_root(&root.path)).collect();
 
-        if let Some(custom_ignore_rule_file) = &self.project_config.custom_ignore_rule_file {
-            walkerbuilder.add_custom_ignore_filename(custom_ignore_rule_file);
-        }
+        // lowered until the scan returns
+        let _io_priority = self.project_config.idle_io_priority.then(IdleIoPriority::enter);
+        let mut throttle = Throttle::new(&self.project_config);
 
         let time_now = Utc::now();
+        let scan_started = Instant::now();
+        let time_limit = self.project_config.max_scan_duration.map(Duration::from_secs);
+        summary.started_at = time_now;
 
-        let mut scanned_entries_total_count = 0;
-        for entry in walkerbuilder.build().filter_map(Result::ok) {
-            let path = entry.path();
+        // Resume an interrupted scan with the same generation, or start a new one
+        let mut checkpoint = match self.load_checkpoint()? {
+            Some(checkpoint) => {
+                println!(
+                    "Resuming scan of {} after {:?}",
+                    self.project_config.id, checkpoint.last_path
+                );
+                summary.resumed = true;
+                summary.entry_count = checkpoint.entry_count;
+                summary.bytes_scanned = checkpoint.bytes_scanned;
+                summary.bytes_allocated = checkpoint.bytes_allocated;
+                summary.hardlink_duplicate_count = checkpoint.hardlink_duplicate_count;
+                checkpoint
+            }
+            None => {
+                let scan_generation = self.next_scan_generation().await?;
+                // Count the documents before the scan, to tell how many entries are new
+                let project_filter = Filter::eq("project_id", self.project_config.id.as_str());
+                ScanCheckpoint {
+                    root: roots[0].path.clone(),
+                    roots: roots.iter().map(|root| root.path.clone()).collect(),
+                    scan_generation,
+                    started_at: time_now,
+                    last_path: None,
+                    entry_count: 0,
+                    bytes_scanned: 0,
+                    bytes_allocated: 0,
+                    hardlink_duplicate_count: 0,
+                    seen_hardlinks: HashSet::new(),
+                    unsaved_hardlinks: Vec::new(),
+                    hardlinks_len: 0,
+                    indexed_count_before: self.count_documents(&project_filter.to_string()).await,
+                }
+            }
+        };
+        let scan_generation = checkpoint.scan_generation;
+        let documents_before = checkpoint.indexed_count_before;
+        summary.scan_generation = scan_generation;
+        if self.checkpoints.is_some() {
+            self.save_checkpoint(&mut checkpoint, summary, None)?;
+        }
+        // Roots are walked one after the other, those before the checkpoint are done
+        let resume_root = roots
+            .iter()
+            .position(|root| root.path == checkpoint.root)
+            .unwrap_or(0);
+        let walked_checkpoint = self.checkpoints.as_ref().map(|_| checkpoint.clone());
+        let walk_builders = roots
+            .iter()
+            .enumerate()
+            .skip(resume_root)
+            .map(|(root_index, root)| {
+                let walked_checkpoint = walked_checkpoint.clone().filter(|_| root_index == resume_root);
+                let mut skipped_roots = skipped_roots.clone();
+                if self.project_config.skip_pseudo_filesystems {
+                    skipped_roots.extend(root_mounts[root_index].pseudo_mount_points());
+                }
+                let walk_builder = self.walk_builder(root, walked_checkpoint, skipped_roots)?;
+                Ok((root_index, walk_builder))
+            })
+            .collect::<Result<Vec<_>, IndexError>>()?;
+        let walks = walk_builders.into_iter().flat_map(|(root_index, walk_builder)| {
+            walk_builder.build().map(move |entry| (root_index, entry))
+        });
+        let mut last_entry_root = resume_root;
+        // the scanned entries as they are sent, each serialized once
+        let mut batch = NdjsonBatch::new(self.gzip_batches);
+        let mut sent_tasks = Vec::new();
+
+        for (walked, (root_index, entry)) in walks.enumerate() {
+            if walked % YIELD_EVERY_ENTRIES == 0 {
+                tokio::task::yield_now().await;
+            }
+            throttle.wait(&self.project_config.id, cancel_token).await;
+            let abort_reason = if cancel_token.is_cancelled() {
+                Some(IndexError::Cancelled)
+            } else {
+                time_limit
+                    .filter(|limit| scan_started.elapsed() >= *limit)
+                    .map(IndexError::TimeLimitExceeded)
+            };
+            if let Some(abort_reason) = abort_reason {
+                // entries already visited are still up-to-date, keep them
+                match self.send_entries_to_meilisearch(&scanned_entries, &mut batch).await {
+                    Ok(_) => {
+                        let last_path = scanned_entries
+                            .last()
+                            .map(|entry| (roots[last_entry_root].path.as_path(), entry.path.as_str()));
+                        self.save_checkpoint(&mut checkpoint, summary, last_path)?;
+                    }
+                    Err(e) => eprintln!("Failed to send the last batch of the aborted scan: {}", e),
+                }
+                return Err(abort_reason);
+            }
 
             // Index both files and folders (ignoring based on the rules)
-            if let Some(index_entry) = self.entry_to_index(&path, &time_now).await {
-                scanned_entries.push(index_entry);
-                scanned_entries_total_count += 1;
+            let (index_entry, walk_error) = match entry {
+                Ok(entry) => match self.entry_to_index(entry.path(), &root_mounts[root_index], &time_now, scan_generation).await {
+                    Ok(index_entry) => (index_entry, None),
+                    Err(e) => (None, Some(WalkError::from_io_error(entry.path(), &e))),
+                },
+                Err(e) => (None, Some(WalkError::from_ignore_error(&e))),
+            };
+            // admins can search for unreadable paths when they are indexed as well
+            let error_entry = walk_error
+                .as_ref()
+                .filter(|_| self.project_config.index_walk_errors)
+                .and_then(|walk_error| {
+                    self.walk_error_to_index(walk_error, &root_mounts[root_index], &time_now, scan_generation)
+                });
+            if let Some(walk_error) = walk_error {
+                summary.record_walk_error(walk_error);
             }
 
-            // Send the batch of 10000 entries to MeiliSearch
-            if scanned_entries.len() >= MEILISEARCH_BATCH_ENTRIES_LIMIT {
-                self.send_entries_to_meilisearch(&scanned_entries).await;
+            let Some(index_entry) = index_entry.or(error_entry) else {
+                continue;
+            };
+            // Send the batch to MeiliSearch before this entry would make it too large
+            let line = serde_json::to_vec(&index_entry)
+                .map_err(|e| IndexError::Meilisearch(meilisearch_sdk::errors::Error::ParseError(e)))?;
+            if !batch.is_empty() && batch.len() + line.len() + 1 > self.batch_max_bytes {
+                // a lost batch would be deleted by the cleanup, so fail the whole scan instead
+                sent_tasks.extend(self.send_entries_to_meilisearch(&scanned_entries, &mut batch).await?);
+                let last_path = scanned_entries
+                    .last()
+                    .map(|entry| (roots[last_entry_root].path.as_path(), entry.path.as_str()));
+                self.save_checkpoint(&mut checkpoint, summary, last_path)?;
                 scanned_entries.clear();
             }
+            // the counts of the checkpoint already include the ancestors of its last path
+            let was_delivered = root_index == resume_root
+                && walked_checkpoint
+                    .as_ref()
+                    .is_some_and(|checkpoint| checkpoint.was_delivered(Path::new(&index_entry.path)));
+            if !was_delivered {
+                let is_new_inode = index_entry
+                    .hardlink_id
+                    .as_ref()
+                    .is_none_or(|hardlink_id| checkpoint.see_hardlink(hardlink_id));
+                if is_new_inode {
+                    summary.bytes_scanned += index_entry.size.unwrap_or(0);
+                    summary.bytes_allocated += index_entry.allocated_size.unwrap_or(0);
+                } else {
+                    summary.hardlink_duplicate_count += 1;
+                }
+                summary.entry_count += 1;
+            }
+            batch
+                .push(&line)
+                .map_err(|e| IndexError::Meilisearch(meilisearch_sdk::errors::Error::Other(Box::new(e))))?;
+            scanned_entries.push(index_entry);
+            last_entry_root = root_index;
         }
 
         // Send remaining entries to MeiliSearch
-        self.send_entries_to_meilisearch(&scanned_entries).await;
+        sent_tasks.extend(self.send_entries_to_meilisearch(&scanned_entries, &mut batch).await?);
+
+        // Tasks of an index are processed in order, once the last batch is done
+        // every entry of this scan is up-to-date and the rest is obselete
+        summary.task_failure_count = self.wait_for_tasks(&sent_tasks).await;
 
-        // Clean obselete index
-        self.clean_obselete_index(&time_now).await;
+        // Entries of a failed batch keep their old generation and would be deleted as
+        // obselete. The next run walks everything again with the same generation instead.
+        if summary.task_failure_count != Some(0) {
+            println!("Skipping cleanup of {}, not every batch arrived", self.project_config.id);
+            checkpoint.root = roots[0].path.clone();
+            checkpoint.last_path = None;
+            checkpoint.forget_hardlinks();
+            self.save_checkpoint(&mut checkpoint, &ScanSummary::new(&self.project_config.id), None)?;
+            return Err(IndexError::TasksFailed(summary.task_failure_count));
+        }
 
-        Ok((scanned_entries, scanned_entries_total_count))
+        // Clean obselete index, only now the scan is complete and the checkpoint can go
+        let cleanup = self.clean_obselete_index(scan_generation, documents_before).await;
+        if matches!(cleanup, Ok(_) | Err(IndexError::TooManyDeletions { .. })) {
+            self.clear_checkpoint()?;
+        }
+        summary.removed_count = cleanup?;
+        if let (Some(before), Some(removed)) = (documents_before, summary.removed_count) {
+            // entries seen again = before - removed, the rest of this scan is new
+            summary.added_count = Some(summary.entry_count.saturating_sub(before.saturating_sub(removed)));
+        }
+        summary.duration = scan_started.elapsed();
+        summary.finished_at = Utc::now();
+
+        Ok(scanned_entries)
+    }
+
+    // Recursively scan the directory
+    // Use WalkBuilder to apply ignore rules efficiently
+    // TODO: maybe record the uuid with modification time and skip ones same as the last-time scan
+    fn walk_builder(
+        &self,
+        root: &RootConfig,
+        walked_checkpoint: Option<ScanCheckpoint>,
+        skipped_roots: Vec<PathBuf>,
+    ) -> Result<WalkBuilder, IndexError> {
+        let project_config = &self.project_config;
+        let mut walkerbuilder = WalkBuilder::new(&root.path);
+        walkerbuilder
+            .standard_filters(false)
+            .follow_links(project_config.follow_symlinks)
+            .same_file_system(project_config.same_file_system);
+        // Shares often hold copies of checkouts without .git, their .gitignore still applies
+        walkerbuilder
+            .git_ignore(project_config.respect_gitignore)
+            .git_exclude(project_config.respect_gitignore)
+            .ignore(project_config.respect_dot_ignore)
+            .git_global(project_config.respect_git_global)
+            .parents(project_config.respect_gitignore || project_config.respect_dot_ignore)
+            .require_git(false);
+
+        let max_depth = root.max_depth.unwrap_or(project_config.max_depth);
+        if max_depth > 0 {
+            walkerbuilder.max_depth(Some(max_depth));
+        } else {
+            walkerbuilder.max_depth(None);
+        }
+
+        let custom_ignore_rule_file = root
+            .custom_ignore_rule_file
+            .as_ref()
+            .or(project_config.custom_ignore_rule_file.as_ref());
+        if let Some(custom_ignore_rule_file) = custom_ignore_rule_file {
+            walkerbuilder.add_custom_ignore_filename(custom_ignore_rule_file);
+        }
+        let overrides = project_config.overrides(&root.path).map_err(IndexError::InvalidGlob)?;
+        walkerbuilder.overrides(overrides);
+
+        if self.checkpoints.is_some() {
+            // a sorted walk has a stable order, so the walked part can be skipped on resume
+            walkerbuilder.sort_by_file_name(|a, b| a.cmp(b));
+        }
+        let exclude_if_present = project_config.exclude_if_present.clone();
+        // Hidden entries are filtered here, the walker's own check is skipped for whitelisted
+        // paths and an include glob like *.pdf would let ._report.pdf through
+        let skip_hidden = !project_config.index_hidden;
+        if walked_checkpoint.is_some()
+            || !skipped_roots.is_empty()
+            || !exclude_if_present.is_empty()
+            || skip_hidden
+        {
+            // the root itself is never filtered, a marker there doesn't empty the project
+            walkerbuilder.filter_entry(move |entry| {
+                let path = entry.path();
+                let is_dir = entry.file_type().is_some_and(|file_type| file_type.is_dir());
+                let has_marker =
+                    is_dir && exclude_if_present.iter().any(|marker| path.join(marker).exists());
+                let is_skipped_hidden =
+                    skip_hidden && entry.file_name().to_string_lossy().starts_with('.');
+                !walked_checkpoint.as_ref().is_some_and(|checkpoint| checkpoint.is_done(path))
+                    && !skipped_roots.iter().any(|root| path == root)
+                    && !has_marker
+                    && !is_skipped_hidden
+            });
+        }
+        Ok(walkerbuilder)
     }
 
-    async fn clean_obselete_index(&self, update_time: &DateTime<Utc>) {
+    // Return the number of deleted entries, None if it is unknown (e.g. spooled to the outbox)
+    // Entries of older generations weren't visited by this scan. Documents indexed
+    // before generations were introduced have none and are migrated by deleting them,
+    // this scan has already re-added every one of them that still exists.
+    async fn clean_obselete_index(
+        &self,
+        scan_generation: u64,
+        indexed_count: Option<usize>,
+    ) -> Result<Option<usize>, IndexError> {
         if let Some(unwrapped_meili_client) = &self.meili_client {
+            let filter = Filter::eq("project_id", self.project_config.id.as_str())
+                .and(
+                    Filter::lt("scan_generation", scan_generation)
+                        .or(Filter::not_exists("scan_generation")),
+                )
+                .to_string();
+            // with a deletion limit, the counts must be known to clean up
+            let limit_deletions = self.project_config.max_delete_percentage.is_some()
+                && !self.project_config.allow_mass_delete;
+            // keep the order with batches still waiting in the outbox
+            if !self.flush_outbox().await {
+                if limit_deletions {
+                    println!("Skipping cleanup of {}, deletions can't be verified", self.project_config.id);
+                    return Ok(None);
+                }
+                self.spool(OutboxBatch::Delete { filter }).await?;
+                return Ok(None);
+            }
+            let obselete_count = self.count_documents(&filter).await;
+            if limit_deletions {
+                // documents with ids of an older scheme were all replaced by this scan,
+                // their deletion is a one-time migration and not a sign of a missing volume
+                let guarded_filter = Filter::eq("project_id", self.project_config.id.as_str())
+                    .and(Filter::lt("scan_generation", scan_generation))
+                    .and(Filter::eq("id_scheme", ID_SCHEME as u64))
+                    .to_string();
+                let obselete_count = self.count_documents(&guarded_filter).await;
+                let (Some(obselete_count), Some(indexed_count)) = (obselete_count, indexed_count)
+                else {
+                    println!("Skipping cleanup of {}, deletions can't be verified", self.project_config.id);
+                    return Ok(None);
+                };
+                let max_percentage = self.project_config.max_delete_percentage.unwrap_or(100.0);
+                if exceeds_delete_percentage(obselete_count, indexed_count, max_percentage) {
+                    return Err(IndexError::TooManyDeletions {
+                        obselete_count,
+                        indexed_count,
+                        max_percentage,
+                    });
+                }
+            }
             let meili_index = unwrapped_meili_client.index(&self.meili_index_name);
-            let delete_operation = DocumentDeletionQuery::new(&meili_index)
-                .with_filter(&format!(
-                    "(project_id = {}) AND (entry_last_updated < {})",
-                    self.project_config.id,
-                    update_time.timestamp()
-                ))
-                .execute::<()>()
+            let mut deletion_query = DocumentDeletionQuery::new(&meili_index);
+            deletion_query.with_filter(&filter);
+            let delete_operation = self
+                .retry_policy
+                .retry("Deleting old index", || deletion_query.execute::<()>())
                 .await;
-            if delete_operation.is_err() {
+            if let Err(e) = delete_operation {
                 eprintln!("Failed to delete old index!");
+                if self.outbox.is_none() {
+                    return Err(e.into());
+                }
+                self.spool(OutboxBatch::Delete { filter }).await?;
+                return Ok(None);
             }
+            return Ok(obselete_count);
         }
+        Ok(None)
     }
 
-    async fn send_entries_to_meilisearch(&self, scanned_entries: &Vec<FileSystemEntry>) {
+    // Return the task of the sent batch, None if nothing was sent directly.
+    // The batch holds the same entries as NDJSON and is empty afterwards.
+    async fn send_entries_to_meilisearch(
+        &self,
+        scanned_entries: &Vec<FileSystemEntry>,
+        batch: &mut NdjsonBatch,
+    ) -> Result<Option<TaskInfo>, IndexError> {
+        let payload = batch.take();
         if let Some(unwrapped_meili_client) = &self.meili_client {
-            let meili_index = unwrapped_meili_client.index(&self.meili_index_name);
-            let create_operation = meili_index.add_documents(scanned_entries, None).await;
-            if create_operation.is_err() {
-                eprintln!("Failed to create new index!");
-                for entry in scanned_entries {
-                    eprintln!("  Failed to index: {:?}", entry.path);
+            if scanned_entries.is_empty() {
+                return Ok(None);
+            }
+            // keep the order with batches still waiting in the outbox
+            if !self.flush_outbox().await {
+                let entries = scanned_entries.clone();
+                self.spool(OutboxBatch::Upsert { entries }).await?;
+                return Ok(None);
+            }
+            let create_operation = match payload {
+                Ok(payload) => {
+                    self.retry_policy
+                        .retry("Sending entries", || {
+                            self.post_documents(unwrapped_meili_client, payload.clone())
+                        })
+                        .await
+                }
+                Err(e) => Err(meilisearch_sdk::errors::Error::Other(Box::new(e))),
+            };
+            match create_operation {
+                Ok(task) => return Ok(Some(task)),
+                Err(e) => {
+                    eprintln!("Failed to create new index!");
+                    if self.outbox.is_none() {
+                        for entry in scanned_entries {
+                            eprintln!("  Failed to index: {:?}", entry.path);
+                        }
+                        return Err(e.into());
+                    }
+                    let entries = scanned_entries.clone();
+                    self.spool(OutboxBatch::Upsert { entries }).await?;
                 }
             }
         }
+        Ok(None)
+    }
+
+    // POST an NDJSON payload of documents like Index::add_documents does with a JSON array.
+    // The SDK can't set a Content-Encoding, so the request is sent with its reqwest directly.
+    async fn post_documents(
+        &self,
+        meili_client: &meilisearch_sdk::client::Client,
+        payload: Bytes,
+    ) -> Result<TaskInfo, meilisearch_sdk::errors::Error> {
+        let url = format!(
+            "{}/indexes/{}/documents",
+            meili_client.get_host().trim_end_matches('/'),
+            self.meili_index_name
+        );
+        let mut request = self
+            .http_client
+            .post(&url)
+            .header(reqwest::header::CONTENT_TYPE, "application/x-ndjson")
+            .body(payload);
+        if self.gzip_batches {
+            request = request.header(reqwest::header::CONTENT_ENCODING, "gzip");
+        }
+        if let Some(api_key) = meili_client.get_api_key() {
+            request = request.bearer_auth(api_key);
+        }
+        let response = request.send().await?;
+        let status = response.status().as_u16();
+        let body = response.text().await?;
+        meilisearch_sdk::request::parse_response(status, 202, &body, url)
+    }
+
+    // Wait for the last task and return how many of the given tasks failed,
+    // None if Meilisearch couldn't tell
+    async fn wait_for_tasks(&self, tasks: &[TaskInfo]) -> Option<usize> {
+        let (Some(meili_client), Some(last_task)) = (&self.meili_client, tasks.last()) else {
+            return Some(0);
+        };
+        let wait_operation = self
+            .retry_policy
+            .retry("Waiting for the last batch", || {
+                last_task
+                    .clone()
+                    .wait_for_completion(meili_client, Some(TASK_POLL_INTERVAL), Some(TASK_TIMEOUT))
+            })
+            .await;
+        if let Err(e) = wait_operation {
+            eprintln!("Failed to wait for the last batch: {}", e);
+            return None;
+        }
+
+        let task_uids: Vec<u32> = tasks.iter().map(|task| task.task_uid).collect();
+        let mut failure_count = 0;
+        for task_uids in task_uids.chunks(100) {
+            let mut tasks_query = TasksSearchQuery::new(meili_client);
+            tasks_query
+                .with_uids(task_uids)
+                .with_statuses(["failed"])
+                .with_limit(0);
+            let failed_tasks = self
+                .retry_policy
+                .retry("Checking the tasks of this scan", || tasks_query.execute())
+                .await;
+            match failed_tasks {
+                Ok(failed_tasks) => failure_count += failed_tasks.total as usize,
+                Err(e) => {
+                    eprintln!("Failed to check the tasks of this scan: {}", e);
+                    return None;
+                }
+            }
+        }
+        if failure_count > 0 {
+            eprintln!("{} batches of {} failed in Meilisearch", failure_count, self.project_config.id);
+        }
+        Some(failure_count)
+    }
+
+    // A checkpoint of other roots (e.g. after a config change) is not resumed
+    fn load_checkpoint(&self) -> Result<Option<ScanCheckpoint>, IndexError> {
+        let Some(checkpoints) = &self.checkpoints else {
+            return Ok(None);
+        };
+        let checkpoint = checkpoints
+            .load(&self.project_config.id)
+            .map_err(IndexError::State)?;
+        let roots: Vec<PathBuf> = self
+            .project_config
+            .roots()
+            .into_iter()
+            .map(|root| root.path)
+            .collect();
+        Ok(checkpoint.filter(|checkpoint| checkpoint.scanned_roots() == roots))
+    }
+
+    fn save_checkpoint(
+        &self,
+        checkpoint: &mut ScanCheckpoint,
+        summary: &ScanSummary,
+        last_path: Option<(&Path, &str)>, // the last delivered entry and its root
+    ) -> Result<(), IndexError> {
+        let Some(checkpoints) = &self.checkpoints else {
+            return Ok(());
+        };
+        if let Some((root, last_path)) = last_path {
+            checkpoint.root = root.to_path_buf();
+            checkpoint.last_path = Some(PathBuf::from(last_path));
+        }
+        checkpoint.entry_count = summary.entry_count;
+        checkpoint.bytes_scanned = summary.bytes_scanned;
+        checkpoint.bytes_allocated = summary.bytes_allocated;
+        checkpoint.hardlink_duplicate_count = summary.hardlink_duplicate_count;
+        checkpoints
+            .save(&self.project_config.id, checkpoint)
+            .map_err(IndexError::State)
+    }
+
+    fn clear_checkpoint(&self) -> Result<(), IndexError> {
+        match &self.checkpoints {
+            Some(checkpoints) => checkpoints
+                .clear(&self.project_config.id)
+                .map_err(IndexError::State),
+            None => Ok(()),
+        }
+    }
+
+    // One more than the last generation of this project, both in the local state and the index
+    async fn next_scan_generation(&self) -> Result<u64, IndexError> {
+        let project_id = &self.project_config.id;
+        let stored_generation = match &self.generations {
+            Some(generations) => generations.last(project_id).map_err(IndexError::State)?,
+            None => 0,
+        };
+        let indexed_generation = self.max_indexed_generation().await.unwrap_or(0);
+        let scan_generation = stored_generation.max(indexed_generation) + 1;
+        // store it before scanning, so an aborted scan never hands out the same generation twice
+        if let Some(generations) = &self.generations {
+            generations
+                .store(project_id, scan_generation)
+                .map_err(IndexError::State)?;
+        }
+        Ok(scan_generation)
+    }
+
+    async fn max_indexed_generation(&self) -> Option<u64> {
+        let meili_client = self.meili_client.as_ref()?;
+        let meili_index = meili_client.index(&self.meili_index_name);
+        let filter = Filter::eq("project_id", self.project_config.id.as_str()).to_string();
+        let sort = ["scan_generation:desc"];
+        let mut search_query = SearchQuery::new(&meili_index);
+        search_query
+            .with_filter(&filter)
+            .with_sort(&sort)
+            .with_limit(1);
+        let search_results = self
+            .retry_policy
+            .retry("Getting the last scan generation", || {
+                search_query.execute::<serde_json::Value>()
+            })
+            .await
+            .ok()?;
+        search_results
+            .hits
+            .first()
+            .and_then(|hit| hit.result["scan_generation"].as_u64())
+    }
+
+    // Count the documents matching the filter, None if Meilisearch can't tell
+    async fn count_documents(&self, filter: &str) -> Option<usize> {
+        let meili_client = self.meili_client.as_ref()?;
+        let meili_index = meili_client.index(&self.meili_index_name);
+        let mut documents_query = DocumentsQuery::new(&meili_index);
+        documents_query
+            .with_filter(filter)
+            .with_limit(1)
+            .with_fields(["uuid"]);
+        self.retry_policy
+            .retry("Counting documents", || {
+                documents_query.execute::<serde_json::Value>()
+            })
+            .await
+            .map(|documents| documents.total as usize)
+            .ok()
+    }
+
+    // Replay the outbox if there is anything pending, return whether it is empty now
+    async fn flush_outbox(&self) -> bool {
+        match (&self.outbox, &self.meili_client) {
+            (Some(outbox), Some(meili_client)) if outbox.has_pending() => outbox
+                .replay(meili_client, &self.meili_index_name, &self.retry_policy)
+                .await
+                .inspect_err(|e| eprintln!("Failed to replay outbox: {}", e))
+                .is_ok(),
+            _ => true,
+        }
+    }
+
+    async fn spool(&self, batch: OutboxBatch) -> Result<(), IndexError> {
+        if let Some(outbox) = &self.outbox {
+            outbox.append(&batch).await.map_err(IndexError::Outbox)?;
+            println!("Meilisearch unavailable, batch spooled to {:?}", outbox.path);
+        }
+        Ok(())
     }
 
     async fn entry_to_index(
         &self,
         path: &Path,
+        mounts: &RootMounts<'_>,
         update_time: &DateTime<Utc>,
-    ) -> Option<FileSystemEntry> {
-        let metadata = fs::metadata(path).ok()?;
-        let name = path.file_name()?.to_string_lossy().to_string();
+        scan_generation: u64,
+    ) -> Result<Option<FileSystemEntry>, io::Error> {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            // a dangling symlink is not followed, so it is not an unreadable path either
+            Err(_) if !self.project_config.follow_symlinks && path.is_symlink() => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let Some(name) = path.file_name() else {
+            return Ok(None);
+        };
+        let name = name.to_string_lossy().to_string();
         let is_hidden = name.starts_with('.');
 
         let entry_type = if path.is_file() {
@@ -241,38 +1077,128 @@ impl Indexer {
         } else if path.is_dir() {
             IndexEntryType::Folder
         } else {
-            return None; // Skip special files like symlinks
+            return Ok(None); // Skip special files like symlinks
         };
 
-        let size = if entry_type == IndexEntryType::File {
-            Some(metadata.len())
+        let (size, allocated_size, hardlink_id) = if entry_type == IndexEntryType::File {
+            let (allocated_size, hardlink_id) = disk_usage(&metadata);
+            (Some(metadata.len()), allocated_size, hardlink_id)
         } else {
-            None // Folders don't have a meaningful size
+            (None, None, None) // Folders don't have a meaningful size
         };
 
-        let modified_date = metadata.modified().ok().and_then(|time| {
+        let modified_date = metadata.modified().ok().map(|time| {
             let datetime: DateTime<Utc> = time.into();
-            Some(datetime)
+            datetime
         });
 
+        // Size and age filters only apply to files, folders are kept to browse the tree
+        if entry_type == IndexEntryType::File {
+            let project_config = &self.project_config;
+            let size = metadata.len();
+            if project_config.min_size.is_some_and(|min_size| size < min_size)
+                || project_config.max_size.is_some_and(|max_size| size > max_size)
+            {
+                return Ok(None);
+            }
+            if let (Some(modified_within), Some(modified_date)) =
+                (project_config.modified_within, modified_date)
+            {
+                let age = update_time.signed_duration_since(modified_date);
+                if age.num_seconds() > modified_within as i64 {
+                    return Ok(None);
+                }
+            }
+        }
+
         let path_str = path.to_string_lossy().to_string();
-        let uuid = Uuid::new_v5(&Uuid::NAMESPACE_URL, path_str.as_bytes()).to_string();
+        let uuid = Uuid::new_v5(&self.id_namespace, path_str.as_bytes()).to_string();
+        let mount = mounts.mount_of(path);
 
-        Some(FileSystemEntry {
+        Ok(Some(FileSystemEntry {
             uuid,
             path: path_str,
             name,
             entry_type,
             size,
+            allocated_size,
             modified_date,
             is_hidden,
             preview: None, // Only relevant for files
             project_id: self.project_config.id.clone(),
             entry_last_updated: update_time.timestamp(),
+            scan_generation,
+            id_scheme: ID_SCHEME,
+            mount_point: mount.map(|mount| mount.mount_point.to_string_lossy().to_string()),
+            fs_type: mount.map(|mount| mount.fs_type.clone()),
+            hardlink_id,
+        }))
+    }
+
+    // An error entry carries the error message as its preview
+    fn walk_error_to_index(
+        &self,
+        walk_error: &WalkError,
+        mounts: &RootMounts<'_>,
+        update_time: &DateTime<Utc>,
+        scan_generation: u64,
+    ) -> Option<FileSystemEntry> {
+        let path_str = walk_error.path.clone()?;
+        let name = Path::new(&path_str)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| path_str.clone());
+        let uuid = Uuid::new_v5(&self.id_namespace, path_str.as_bytes()).to_string();
+        let mount = mounts.mount_of(Path::new(&path_str));
+
+        Some(FileSystemEntry {
+            uuid,
+            path: path_str,
+            is_hidden: name.starts_with('.'),
+            name,
+            entry_type: IndexEntryType::Error,
+            size: None,
+            allocated_size: None,
+            modified_date: None,
+            preview: Some(format!("{}: {}", walk_error.kind, walk_error.message)),
+            project_id: self.project_config.id.clone(),
+            entry_last_updated: update_time.timestamp(),
+            scan_generation,
+            id_scheme: ID_SCHEME,
+            mount_point: mount.map(|mount| mount.mount_point.to_string_lossy().to_string()),
+            fs_type: mount.map(|mount| mount.fs_type.clone()),
+            hardlink_id: None,
         })
     }
 }
 
+// The bytes allocated on disk and the id shared by the hardlinks of a file
+#[cfg(unix)]
+fn disk_usage(metadata: &fs::Metadata) -> (Option<u64>, Option<String>) {
+    use std::os::unix::fs::MetadataExt;
+    // st_blocks is in 512-byte units whatever the block size of the filesystem
+    let allocated_size = metadata.blocks() * 512;
+    let hardlink_id = (metadata.nlink() > 1).then(|| format!("{}:{}", metadata.dev(), metadata.ino()));
+    (Some(allocated_size), hardlink_id)
+}
+
+#[cfg(not(unix))]
+fn disk_usage(_metadata: &fs::Metadata) -> (Option<u64>, Option<String>) {
+    (None, None)
+}
+
+// Whether deleting obselete_count of indexed_count entries is more than max_percentage
+pub fn exceeds_delete_percentage(
+    obselete_count: usize,
+    indexed_count: usize,
+    max_percentage: f64,
+) -> bool {
+    if indexed_count == 0 {
+        return false;
+    }
+    obselete_count as f64 * 100.0 / indexed_count as f64 > max_percentage
+}
+
 pub async fn is_meilisearch_running(meilisearch_config: &MeiliSearchConfig) -> bool {
     let meilisearch_url = &meilisearch_config.meilisearch_url;
     let meilisearch_api_key = &meilisearch_config.meilisearch_api_key;
@@ -282,6 +1208,6 @@ pub async fn is_meilisearch_running(meilisearch_config: &MeiliSearchConfig) -> b
     if let Ok(meili_client) = meili_client {
         return meili_client.health().await.is_ok();
     } else {
-        return false;
+        false
     }
 }
@@ -1,19 +1,34 @@
+mod check;
+mod checkpoint;
 mod config;
+mod explain;
 mod file_index;
+mod filter;
+mod generations;
+mod history;
+mod hooks;
 mod indexer;
+mod mounts;
+mod ndjson;
+mod orphans;
+mod outbox;
+mod presets;
+mod reload;
+mod retry;
 mod scheduler;
 mod server;
+mod throttle;
 
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::process::Command;
 use tokio::signal;
 use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
 use clap::{Arg, Command as ClapCommand};
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() {
-    // 解析命令行参数
-    let matches = ClapCommand::new("MeiliFileFinder")
+fn cli() -> ClapCommand {
+    ClapCommand::new("MeiliFileFinder")
         .version("0.1.0")
         .about("A file indexing and search tool using Meilisearch")
         .arg(
@@ -22,40 +37,201 @@ async fn main() {
                 .long("conf")
                 .value_name("FILE")
                 .help("Sets a custom config file path")
-                .required(false),
+                .required(false)
+                .global(true),
+        )
+        .subcommand(
+            ClapCommand::new("check-config")
+                .about("Checks the config file and reports every problem found"),
         )
-        .get_matches();
+        .subcommand(
+            ClapCommand::new("explain-ignore")
+                .about("Shows which ignore rule keeps a path out of the index")
+                .arg(
+                    Arg::new("path")
+                        .value_name("PATH")
+                        .required(true)
+                        .help("Path inside the root of a project"),
+                )
+                .arg(
+                    Arg::new("project")
+                        .short('p')
+                        .long("project")
+                        .value_name("ID")
+                        .help("Only check this project"),
+                ),
+        )
+        .subcommand(
+            ClapCommand::new("history")
+                .about("Shows the latest scans recorded in the data directory")
+                .arg(
+                    Arg::new("project")
+                        .short('p')
+                        .long("project")
+                        .value_name("ID")
+                        .help("Only show scans of this project"),
+                )
+                .arg(
+                    Arg::new("limit")
+                        .short('n')
+                        .long("limit")
+                        .value_name("COUNT")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("20")
+                        .help("Number of scans to show"),
+                ),
+        )
+}
+
+fn print_ignore_explanation(config: &config::Config, explain_matches: &clap::ArgMatches) {
+    let path = std::path::Path::new(explain_matches.get_one::<String>("path").unwrap());
+    let project_id = explain_matches.get_one::<String>("project");
+    let projects: Vec<&config::ProjectConfig> = config
+        .projects
+        .iter()
+        .filter(|project| project_id.is_none_or(|id| project.id == *id))
+        .filter(|project| {
+            project_id.is_some() || project.roots().iter().any(|root| path.starts_with(&root.path))
+        })
+        .collect();
+    if projects.is_empty() {
+        eprintln!("No project contains {:?}", path);
+        std::process::exit(1);
+    }
+    for project in projects {
+        match explain::explain_ignored(project, path) {
+            Ok(Some(ignored)) => println!("{}: {}", project.id, ignored),
+            Ok(None) => println!("{}: {:?} is not ignored by any rule", project.id, path),
+            Err(e) => println!("{}: {}", project.id, e),
+        }
+    }
+}
+
+fn print_history(config: &config::Config, history_matches: &clap::ArgMatches) {
+    let Some(data_dir) = &config.data_dir else {
+        eprintln!("No data_dir configured, the scan history is not recorded.");
+        std::process::exit(1);
+    };
+    let project_id = history_matches.get_one::<String>("project").map(String::as_str);
+    let limit = *history_matches.get_one::<usize>("limit").unwrap();
+    let summaries = match history::ScanHistory::new(data_dir).read(project_id, limit) {
+        Ok(summaries) => summaries,
+        Err(e) => {
+            eprintln!("Failed to read scan history: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    println!(
+        "{:<25} {:<16} {:<10} {:>10} {:>14} {:>8} {:>8} {:>7} {:>9}",
+        "Started", "Project", "Status", "Entries", "Bytes", "Added", "Removed", "Errors", "Duration"
+    );
+    let optional_count = |count: Option<usize>| count.map_or("-".to_string(), |c| c.to_string());
+    for summary in summaries {
+        println!(
+            "{:<25} {:<16} {:<10} {:>10} {:>14} {:>8} {:>8} {:>7} {:>8.1}s",
+            summary.started_at.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+            summary.project_id,
+            format!("{:?}", summary.status),
+            summary.entry_count,
+            summary.bytes_scanned,
+            optional_count(summary.added_count),
+            optional_count(summary.removed_count),
+            summary.walk_error_count + summary.task_failure_count.unwrap_or(0) + summary.errors.len(),
+            summary.duration.as_secs_f64(),
+        );
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    // 解析命令行参数
+    let matches = cli().get_matches();
 
     // 获取配置文件路径，如果没有则显示帮助信息
     let config_path = match matches.get_one::<String>("config") {
         Some(path) => path.as_str(),
         None => {
-            println!("{}", ClapCommand::new("MeiliFileFinder")
-                .version("0.1.0")
-                .about("A file indexing and search tool using Meilisearch")
-                .arg(
-                    Arg::new("config")
-                        .short('c')
-                        .long("conf")
-                        .value_name("FILE")
-                        .help("Sets a custom config file path")
-                        .required(false),
-                )
-                .render_help());
+            println!("{}", cli().render_help());
             std::process::exit(1);
         }
     };
 
+    if matches.subcommand_matches("check-config").is_some() {
+        let diagnostics = check::check_config(config_path);
+        for diagnostic in &diagnostics {
+            match &diagnostic.file {
+                Some(file) => println!("{}: {}", file.display(), diagnostic),
+                None => println!("{}: {}", config_path, diagnostic),
+            }
+        }
+        let problem_count = diagnostics.iter().filter(|diagnostic| !diagnostic.warning).count();
+        if problem_count > 0 {
+            eprintln!("{} problems found in {}", problem_count, config_path);
+            std::process::exit(1);
+        }
+        println!("{} is valid", config_path);
+        return;
+    }
+
     // Read Config
     let config = config::read_config(config_path).expect("Failed to read config file");
+
+    if let Some(explain_matches) = matches.subcommand_matches("explain-ignore") {
+        print_ignore_explanation(&config, explain_matches);
+        return;
+    }
+
+    if let Some(history_matches) = matches.subcommand_matches("history") {
+        print_history(&config, history_matches);
+        return;
+    }
+
     println!("Config Loaded!\n");
     println!("{:}", config);
+    for project in &config.projects {
+        for nested_root in config::nested_roots(&config.projects, project) {
+            println!(
+                "The root {:?} of another project lies inside {}, {}",
+                nested_root,
+                project.id,
+                if project.skip_nested_projects {
+                    "it is skipped by the outer project"
+                } else {
+                    "both projects index it with their own document ids"
+                }
+            );
+        }
+    }
 
     let meilisearch_child= check_and_start_meilisearch(&config.meilisearch).await;
 
-    let server = server::start_server(&config.meilisearch);
+    if let Err(e) = orphans::handle_orphaned_projects(&config).await {
+        eprintln!("Failed to check the index for orphaned projects: {}", e);
+    }
 
-    let scheduler = scheduler::schedule_projects(&config.projects, &config.meilisearch);
+    // Cancelled on shutdown so that running scans abort without cleaning the index
+    let shutdown_token = CancellationToken::new();
+    let scan_registry = Arc::new(scheduler::ScanRegistry::new(shutdown_token.clone()));
+
+    let server = server::start_server(
+        &config.meilisearch,
+        &config.server,
+        config.data_dir.as_deref(),
+        scan_registry.clone(),
+    );
+
+    // SIGHUP and changes of the config file reschedule the projects
+    let (config_sender, config_updates) = tokio::sync::watch::channel(config.clone());
+    tokio::spawn(reload::watch_config(config_path.to_string(), config_sender));
+
+    let scheduler = scheduler::schedule_projects(
+        &config.projects,
+        &config.meilisearch,
+        config.data_dir.as_deref(),
+        scan_registry.clone(),
+        config_updates,
+    );
 
     // Join the server, scheduler, and signal handler
     tokio::select! {
@@ -69,7 +245,7 @@ async fn main() {
             }
         } => {},
         // Signal Handler
-        _ = unified_signal_handler(meilisearch_child) => println!(),
+        _ = unified_signal_handler(meilisearch_child, shutdown_token, scan_registry) => println!(),
     }
 }
 
@@ -77,7 +253,7 @@ async fn check_and_start_meilisearch(
     meilisearch_config: &config::MeiliSearchConfig,
 ) -> Option<Arc<Mutex<tokio::process::Child>>> {
     // Check if Meilisearch is running and start if necessary
-    if !indexer::is_meilisearch_running(&meilisearch_config).await {
+    if !indexer::is_meilisearch_running(meilisearch_config).await {
         println!("No available Meilisearch Instance.");
 
         // parse startup configs
@@ -106,14 +282,14 @@ async fn check_and_start_meilisearch(
         let meilisearch_telemetry = meilisearch_config.meilisearch_telemetry;
 
         // assign environemnt variables for child_builder
-        let mut child_builder = Command::new(&meilisearch_bin_path);
+        let mut child_builder = Command::new(meilisearch_bin_path);
         child_builder.kill_on_drop(true); // Automatically kill Meilisearch on parent exit
         child_builder.env("MEILI_HTTP_ADDR", meilisearch_url_no_prefix);
         child_builder.env("MEILI_MASTER_KEY", meilisearch_master_key);
         if !meilisearch_db_path.is_empty() {
             child_builder.env("MEILI_DB_PATH", meilisearch_db_path);
         }
-        if meilisearch_telemetry == false {
+        if !meilisearch_telemetry {
             child_builder.arg("--no-analytics");
         }
 
@@ -127,7 +303,7 @@ async fn check_and_start_meilisearch(
         let max_attempts = 30;
         let delay = tokio::time::Duration::from_secs(1);
         loop {
-            if indexer::is_meilisearch_running(&meilisearch_config).await {
+            if indexer::is_meilisearch_running(meilisearch_config).await {
                 println!("Meilisearch is ready!");
                 break;
             }
@@ -145,7 +321,14 @@ async fn check_and_start_meilisearch(
     }
 }
 
-async fn unified_signal_handler(meilisearch_child: Option<Arc<Mutex<tokio::process::Child>>>) {
+// how long running scans get to stop on shutdown
+const SHUTDOWN_SCAN_TIMEOUT: Duration = Duration::from_secs(30);
+
+async fn unified_signal_handler(
+    meilisearch_child: Option<Arc<Mutex<tokio::process::Child>>>,
+    shutdown_token: CancellationToken,
+    scan_registry: Arc<scheduler::ScanRegistry>,
+) {
     // Handle cross-platform `CTRL+C`
     let ctrl_c = async {
         signal::ctrl_c().await.expect("Failed to listen for CTRL+C");
@@ -159,13 +342,10 @@ async fn unified_signal_handler(meilisearch_child: Option<Arc<Mutex<tokio::proce
             .expect("Failed to register SIGTERM handler");
         let mut sigint = signal::unix::signal(signal::unix::SignalKind::interrupt())
             .expect("Failed to register SIGINT handler");
-        let mut sighup = signal::unix::signal(signal::unix::SignalKind::hangup())
-            .expect("Failed to register SIGHUP handler");
 
         tokio::select! {
             _ = sigterm.recv() => println!("SIGTERM received. Terminating child process..."),
             _ = sigint.recv() => println!("SIGINT received. Terminating child process..."),
-            _ = sighup.recv() => println!("SIGHUP received. Terminating child process..."),
         }
     };
 
@@ -181,6 +361,16 @@ async fn unified_signal_handler(meilisearch_child: Option<Arc<Mutex<tokio::proce
         _ = unix_signals => {},
     }
 
+    // Abort running scans before Meilisearch goes away, they still send their
+    // last batch and save a checkpoint to resume from
+    shutdown_token.cancel();
+    if !scan_registry.wait_until_idle(SHUTDOWN_SCAN_TIMEOUT).await {
+        eprintln!(
+            "Scans of {:?} didn't stop in time, they resume from their last checkpoint",
+            scan_registry.running_projects()
+        );
+    }
+
     // Kill the child process if running
     if let Some(child_mutex) = meilisearch_child {
         let mut child = child_mutex.lock().await;
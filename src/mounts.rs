@@ -0,0 +1,161 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+#[path = "tests/mounts_tests.rs"]
+mod mounts_tests;
+
+const MOUNTINFO_PATH: &str = "/proc/self/mountinfo";
+
+// Filesystems without files worth indexing, or overlays repeating those of another mount
+const PSEUDO_FS_TYPES: &[&str] = &[
+    "proc",
+    "sysfs",
+    "devtmpfs",
+    "devpts",
+    "tmpfs",
+    "ramfs",
+    "cgroup",
+    "cgroup2",
+    "securityfs",
+    "debugfs",
+    "tracefs",
+    "pstore",
+    "efivarfs",
+    "bpf",
+    "mqueue",
+    "hugetlbfs",
+    "configfs",
+    "fusectl",
+    "autofs",
+    "binfmt_misc",
+    "rpc_pipefs",
+    "nsfs",
+    "overlay",
+    "fuse.lxcfs",
+    "fuse.gvfsd-fuse",
+    "fuse.portal",
+    "fuse.snapfuse",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mount {
+    pub mount_point: PathBuf,
+    pub fs_type: String,
+}
+
+impl Mount {
+    pub fn is_pseudo(&self) -> bool {
+        PSEUDO_FS_TYPES.contains(&self.fs_type.as_str())
+    }
+}
+
+// The mounts of this process, read once per scan
+#[derive(Debug, Clone, Default)]
+pub struct MountTable {
+    mounts: Vec<Mount>,
+}
+
+impl MountTable {
+    pub fn read() -> io::Result<Self> {
+        fs::read_to_string(MOUNTINFO_PATH).map(|content| Self::parse(&content))
+    }
+
+    // Lines of /proc/self/mountinfo look like
+    // 36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue
+    // with the mount point as the 5th field and the filesystem type after the "-"
+    pub fn parse(content: &str) -> Self {
+        let mounts = content
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split(' ');
+                let mount_point = fields.nth(4)?;
+                let fs_type = fields.skip_while(|field| *field != "-").nth(1)?;
+                Some(Mount {
+                    mount_point: PathBuf::from(unescape(mount_point)),
+                    fs_type: fs_type.to_string(),
+                })
+            })
+            .collect();
+        MountTable { mounts }
+    }
+
+    // The mount a path lives on, the last one wins when mounts are stacked
+    pub fn mount_of(&self, path: &Path) -> Option<&Mount> {
+        self.mounts
+            .iter()
+            .filter(|mount| path.starts_with(&mount.mount_point))
+            .max_by_key(|mount| mount.mount_point.components().count())
+    }
+
+    // The mounts as seen from a root written in the config, e.g. a relative ./frontend
+    pub fn for_root(&self, root: &Path) -> RootMounts<'_> {
+        RootMounts {
+            table: self,
+            root: root.to_path_buf(),
+            resolved_root: fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf()),
+        }
+    }
+}
+
+// Walk paths keep the root as written, mount points are absolute and resolved.
+// The root is resolved once and the paths below it are matched relative to it.
+pub struct RootMounts<'a> {
+    table: &'a MountTable,
+    root: PathBuf,
+    resolved_root: PathBuf,
+}
+
+impl RootMounts<'_> {
+    pub fn mount_of(&self, path: &Path) -> Option<&Mount> {
+        match path.strip_prefix(&self.root) {
+            Ok(relative) => self.table.mount_of(&self.resolved_root.join(relative)),
+            Err(_) => self.table.mount_of(path),
+        }
+    }
+
+    // Pseudo filesystems mounted below the root, as walk paths. The mount of the root
+    // itself is never skipped, e.g. a root inside a container's overlay. Only the last
+    // of stacked mounts counts, e.g. an autofs trigger with the nfs4 share on top of it.
+    pub fn pseudo_mount_points(&self) -> Vec<PathBuf> {
+        let mounts = &self.table.mounts;
+        mounts
+            .iter()
+            .enumerate()
+            .filter(|(i, mount)| {
+                mounts[i + 1..]
+                    .iter()
+                    .all(|later| later.mount_point != mount.mount_point)
+            })
+            .map(|(_, mount)| mount)
+            .filter(|mount| mount.is_pseudo())
+            .filter_map(|mount| mount.mount_point.strip_prefix(&self.resolved_root).ok())
+            .filter(|relative| !relative.as_os_str().is_empty())
+            .map(|relative| self.root.join(relative))
+            .collect()
+    }
+}
+
+// Spaces, tabs, newlines and backslashes of mount points are escaped as octal, e.g. \040
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes.get(i + 1..i + 4).filter(|digits| {
+            bytes[i] == b'\\' && digits.iter().all(|digit| (b'0'..=b'7').contains(digit))
+        });
+        match octal.and_then(|digits| u8::from_str_radix(std::str::from_utf8(digits).ok()?, 8).ok()) {
+            Some(byte) => {
+                unescaped.push(byte);
+                i += 4;
+            }
+            None => {
+                unescaped.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&unescaped).into_owned()
+}
@@ -0,0 +1,70 @@
+use bytes::Bytes;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{self, Write};
+use std::mem;
+
+#[cfg(test)]
+#[path = "tests/ndjson_tests.rs"]
+mod ndjson_tests;
+
+// A batch of documents as NDJSON, one per line, written while the entries are scanned.
+// Compressed on the fly, so only the gzipped payload is kept in memory.
+pub struct NdjsonBatch {
+    writer: NdjsonWriter,
+    len: usize, // uncompressed bytes written so far
+}
+
+enum NdjsonWriter {
+    Plain(Vec<u8>),
+    Gzip(GzEncoder<Vec<u8>>),
+}
+
+impl NdjsonBatch {
+    pub fn new(gzip: bool) -> Self {
+        let writer = if gzip {
+            NdjsonWriter::Gzip(GzEncoder::new(Vec::new(), Compression::fast()))
+        } else {
+            NdjsonWriter::Plain(Vec::new())
+        };
+        NdjsonBatch { writer, len: 0 }
+    }
+
+    pub fn is_gzip(&self) -> bool {
+        matches!(self.writer, NdjsonWriter::Gzip(_))
+    }
+
+    // The uncompressed size, what Meilisearch's payload limit applies to
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Append a serialized document, the newline is added here
+    pub fn push(&mut self, line: &[u8]) -> io::Result<()> {
+        match &mut self.writer {
+            NdjsonWriter::Plain(payload) => {
+                payload.extend_from_slice(line);
+                payload.push(b'\n');
+            }
+            NdjsonWriter::Gzip(encoder) => {
+                encoder.write_all(line)?;
+                encoder.write_all(b"\n")?;
+            }
+        }
+        self.len += line.len() + 1;
+        Ok(())
+    }
+
+    // The payload of the batch, which starts over empty
+    pub fn take(&mut self) -> io::Result<Bytes> {
+        let batch = mem::replace(self, NdjsonBatch::new(self.is_gzip()));
+        match batch.writer {
+            NdjsonWriter::Plain(payload) => Ok(Bytes::from(payload)),
+            NdjsonWriter::Gzip(encoder) => encoder.finish().map(Bytes::from),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::config::{Config, OrphanedProjects};
+use crate::filter::Filter;
+use crate::indexer::{IndexError, TASK_POLL_INTERVAL, TASK_TIMEOUT};
+use crate::retry::RetryPolicy;
+use meilisearch_sdk::documents::{DocumentDeletionQuery, DocumentsQuery};
+use meilisearch_sdk::indexes::Index;
+use meilisearch_sdk::search::{SearchQuery, Selectors};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+// documents moved to another project per update
+const MIGRATION_BATCH_SIZE: usize = 1000;
+
+// A partial update of a document, moving it to another project.
+// Its id was derived for the old project, so it is replaced by the next scan
+// of the new project without counting against max_delete_percentage.
+#[derive(Serialize)]
+struct ProjectMigration<'a> {
+    uuid: String,
+    project_id: &'a str,
+    id_scheme: u32,
+}
+
+// Handle documents of project ids that are not configured anymore,
+// either renamed (previous_ids) or removed projects
+pub async fn handle_orphaned_projects(config: &Config) -> Result<(), IndexError> {
+    let meilisearch_config = &config.meilisearch;
+    let meili_client = meilisearch_sdk::client::Client::new(
+        &meilisearch_config.meilisearch_url,
+        Some(&meilisearch_config.meilisearch_api_key),
+    )?;
+    let meili_index = meili_client.index(&meilisearch_config.meilisearch_index_name);
+    let retry_policy = RetryPolicy::from_config(meilisearch_config);
+
+    let configured_ids: HashSet<&str> = config.projects.iter().map(|p| p.id.as_str()).collect();
+    let orphaned_projects = indexed_projects(&meili_index, &retry_policy)
+        .await?
+        .into_iter()
+        .filter(|(project_id, _)| !configured_ids.contains(project_id.as_str()));
+
+    for (orphaned_id, document_count) in orphaned_projects {
+        let renamed_to = config
+            .projects
+            .iter()
+            .find(|project| project.previous_ids.contains(&orphaned_id));
+        if let Some(project) = renamed_to {
+            println!(
+                "Moving {} documents of {} to the renamed project {}",
+                document_count, orphaned_id, project.id
+            );
+            migrate_project(&meili_index, &retry_policy, &orphaned_id, &project.id).await?;
+            continue;
+        }
+        match config.orphaned_projects {
+            OrphanedProjects::Report => println!(
+                "{} documents of the unconfigured project {} are left in the index, \
+                 set orphaned_projects = \"delete\" or previous_ids to handle them",
+                document_count, orphaned_id
+            ),
+            OrphanedProjects::Delete => {
+                println!("Deleting {} documents of the unconfigured project {}", document_count, orphaned_id);
+                let filter = Filter::eq("project_id", orphaned_id.as_str()).to_string();
+                let mut deletion_query = DocumentDeletionQuery::new(&meili_index);
+                deletion_query.with_filter(&filter);
+                retry_policy
+                    .retry("Deleting orphaned project", || deletion_query.execute::<()>())
+                    .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// The project ids in the index with their document counts, from the project_id facet.
+// Meilisearch returns at most maxValuesPerFacet (100 by default) of them.
+async fn indexed_projects(
+    meili_index: &Index,
+    retry_policy: &RetryPolicy,
+) -> Result<BTreeMap<String, usize>, IndexError> {
+    let facets = ["project_id"];
+    let mut search_query = SearchQuery::new(meili_index);
+    search_query
+        .with_facets(Selectors::Some(&facets))
+        .with_limit(0);
+    let search_results = retry_policy
+        .retry("Getting indexed projects", || {
+            search_query.execute::<serde_json::Value>()
+        })
+        .await?;
+    Ok(search_results
+        .facet_distribution
+        .and_then(|mut distribution| distribution.remove("project_id"))
+        .map(|project_ids| project_ids.into_iter().collect())
+        .unwrap_or_default())
+}
+
+// Move the documents in batches, each one leaves the filter once its update is done
+async fn migrate_project(
+    meili_index: &Index,
+    retry_policy: &RetryPolicy,
+    old_id: &str,
+    new_id: &str,
+) -> Result<(), IndexError> {
+    let filter = Filter::eq("project_id", old_id).to_string();
+    loop {
+        let mut documents_query = DocumentsQuery::new(meili_index);
+        documents_query
+            .with_filter(&filter)
+            .with_limit(MIGRATION_BATCH_SIZE)
+            .with_fields(["uuid"]);
+        let documents = retry_policy
+            .retry("Getting documents to migrate", || {
+                documents_query.execute::<serde_json::Value>()
+            })
+            .await?;
+        let migrations: Vec<ProjectMigration> = documents
+            .results
+            .iter()
+            .filter_map(|document| document["uuid"].as_str())
+            .map(|uuid| ProjectMigration {
+                uuid: uuid.to_string(),
+                project_id: new_id,
+                id_scheme: 0,
+            })
+            .collect();
+        if migrations.is_empty() {
+            return Ok(());
+        }
+        let task = retry_policy
+            .retry("Migrating documents", || {
+                meili_index.add_or_update(&migrations, Some("uuid"))
+            })
+            .await?
+            .wait_for_completion(&meili_index.client, Some(TASK_POLL_INTERVAL), Some(TASK_TIMEOUT))
+            .await?;
+        // a failed batch would be fetched again forever
+        if task.is_failure() {
+            return Err(meilisearch_sdk::errors::Error::from(task.unwrap_failure()).into());
+        }
+    }
+}
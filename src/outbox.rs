@@ -0,0 +1,142 @@
+use crate::file_index::FileSystemEntry;
+use crate::retry::RetryPolicy;
+use meilisearch_sdk::client::Client;
+use meilisearch_sdk::documents::DocumentDeletionQuery;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
+
+#[cfg(test)]
+#[path = "tests/outbox_tests.rs"]
+mod outbox_tests;
+
+// Serializes appends and replays, otherwise batches could be lost, sent twice or out of order
+static REPLAY_LOCK: Mutex<()> = Mutex::const_new(());
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum OutboxBatch {
+    Upsert { entries: Vec<FileSystemEntry> },
+    Delete { filter: String },
+}
+
+// Append-only journal of batches that could not be delivered to Meilisearch.
+// Each line is one batch in JSON, batches are replayed in the order they were spooled.
+#[derive(Debug, Clone)]
+pub struct Outbox {
+    pub path: PathBuf,
+}
+
+impl Outbox {
+    pub fn new(data_dir: &Path, index_name: &str) -> Self {
+        Outbox {
+            path: data_dir.join("outbox").join(format!("{}.ndjson", index_name)),
+        }
+    }
+
+    pub fn has_pending(&self) -> bool {
+        fs::metadata(&self.path).is_ok_and(|metadata| metadata.len() > 0)
+    }
+
+    pub async fn append(&self, batch: &OutboxBatch) -> io::Result<()> {
+        let _guard = REPLAY_LOCK.lock().await;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut line = serde_json::to_vec(batch)?;
+        line.push(b'\n');
+        let mut journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        journal.write_all(&line)?;
+        journal.sync_data()
+    }
+
+    pub fn pending(&self) -> io::Result<Vec<OutboxBatch>> {
+        let journal = match File::open(&self.path) {
+            Ok(journal) => journal,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut batches = Vec::new();
+        for line in BufReader::new(journal).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(batch) => batches.push(batch),
+                // a crash while appending leaves a truncated last line, nothing to replay there
+                Err(e) => eprintln!("Skipping corrupted outbox entry in {:?}: {}", self.path, e),
+            }
+        }
+        Ok(batches)
+    }
+
+    // Replace the journal with the given batches, atomically
+    fn rewrite(&self, batches: &[OutboxBatch]) -> io::Result<()> {
+        if batches.is_empty() {
+            return match fs::remove_file(&self.path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+        let temp_path = self.path.with_extension("ndjson.tmp");
+        let mut temp_journal = File::create(&temp_path)?;
+        for batch in batches {
+            let mut line = serde_json::to_vec(batch)?;
+            line.push(b'\n');
+            temp_journal.write_all(&line)?;
+        }
+        temp_journal.sync_data()?;
+        fs::rename(&temp_path, &self.path)
+    }
+
+    // Send spooled batches in order, stop at the first failure and keep the rest.
+    // Return the number of replayed batches.
+    pub async fn replay(
+        &self,
+        meili_client: &Client,
+        index_name: &str,
+        retry_policy: &RetryPolicy,
+    ) -> Result<usize, meilisearch_sdk::errors::Error> {
+        let _guard = REPLAY_LOCK.lock().await;
+        let batches = self.pending().map_err(io_to_meilisearch_error)?;
+        let meili_index = meili_client.index(index_name);
+        for (replayed, batch) in batches.iter().enumerate() {
+            let result = match batch {
+                OutboxBatch::Upsert { entries } => retry_policy
+                    .retry("Replaying upsert batch", || {
+                        meili_index.add_documents(entries, None)
+                    })
+                    .await
+                    .map(|_| ()),
+                OutboxBatch::Delete { filter } => {
+                    let mut deletion_query = DocumentDeletionQuery::new(&meili_index);
+                    deletion_query.with_filter(filter);
+                    retry_policy
+                        .retry("Replaying delete batch", || deletion_query.execute::<()>())
+                        .await
+                        .map(|_| ())
+                }
+            };
+            if let Err(e) = result {
+                self.rewrite(&batches[replayed..])
+                    .map_err(io_to_meilisearch_error)?;
+                return Err(e);
+            }
+        }
+        self.rewrite(&[]).map_err(io_to_meilisearch_error)?;
+        if !batches.is_empty() {
+            println!("Replayed {} batches from {:?}", batches.len(), self.path);
+        }
+        Ok(batches.len())
+    }
+}
+
+fn io_to_meilisearch_error(e: io::Error) -> meilisearch_sdk::errors::Error {
+    meilisearch_sdk::errors::Error::Other(Box::new(e))
+}
@@ -0,0 +1,42 @@
+// Built-in ignore presets for the clutter NAS systems and desktop clients leave on shares.
+// The globs are gitignore-style, a trailing / only matches folders.
+pub const IGNORE_PRESETS: &[(&str, &[&str])] = &[
+    (
+        "synology",
+        &["@eaDir/", "\\#recycle/", "\\#snapshot/", "@tmp/", ".SynologyWorkingDirectory/"],
+    ),
+    (
+        "qnap",
+        &[".@__thumb/", "@Recycle/", "@Recently-Snapshot/", ".@upload_cache/", ".@__qini/"],
+    ),
+    (
+        "macos",
+        &[
+            ".DS_Store",
+            "._*",
+            ".AppleDouble/",
+            ".AppleDB/",
+            ".Spotlight-V100/",
+            ".Trashes/",
+            ".fseventsd/",
+            ".TemporaryItems/",
+            ".DocumentRevisions-V100/",
+        ],
+    ),
+    (
+        "windows",
+        &["Thumbs.db", "ehthumbs.db", "desktop.ini", "$RECYCLE.BIN/", "System Volume Information/"],
+    ),
+    ("snapshots", &[".zfs/", ".snapshot/", ".snapshots/"]),
+];
+
+pub fn preset_globs(name: &str) -> Option<&'static [&'static str]> {
+    IGNORE_PRESETS
+        .iter()
+        .find(|(preset, _)| *preset == name)
+        .map(|(_, globs)| *globs)
+}
+
+pub fn preset_names() -> Vec<&'static str> {
+    IGNORE_PRESETS.iter().map(|(preset, _)| *preset).collect()
+}
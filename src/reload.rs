@@ -0,0 +1,68 @@
+use crate::config::{self, Config};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+
+// how often to check whether the config file was modified
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// The config file and its included files with their modification times
+fn config_files(config_path: &str, config: &Config) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let config_dir = Path::new(config_path).parent().unwrap_or(Path::new("."));
+    let mut paths = vec![PathBuf::from(config_path)];
+    for pattern in &config.include {
+        paths.extend(config::include_paths(pattern, config_dir).unwrap_or_default());
+    }
+    paths
+        .into_iter()
+        .map(|path| {
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+            (path, modified)
+        })
+        .collect()
+}
+
+// Re-read the config on SIGHUP or once it or an included file was modified, added or removed,
+// and publish it if it is valid. Only projects are applied, other settings need a restart.
+pub async fn watch_config(config_path: String, config_sender: watch::Sender<Config>) {
+    let mut last_modified = config_files(&config_path, &config_sender.borrow());
+
+    #[cfg(unix)]
+    let mut sighup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        .expect("Failed to register SIGHUP handler");
+
+    loop {
+        #[cfg(unix)]
+        let hangup = async {
+            sighup.recv().await;
+        };
+        #[cfg(not(unix))]
+        let hangup = std::future::pending::<()>();
+
+        tokio::select! {
+            _ = hangup => println!("SIGHUP received. Reloading {}...", config_path),
+            _ = tokio::time::sleep(CONFIG_POLL_INTERVAL) => {
+                let modified = config_files(&config_path, &config_sender.borrow());
+                if modified == last_modified {
+                    continue;
+                }
+                println!("{} was modified. Reloading...", config_path);
+            }
+        }
+        // a broken edit is only reported once, the next change is read again
+        last_modified = config_files(&config_path, &config_sender.borrow());
+
+        match config::read_config(&config_path) {
+            Ok(config) => {
+                // the new includes are watched from now on
+                last_modified = config_files(&config_path, &config);
+                if config_sender.send(config).is_err() {
+                    return;
+                }
+            }
+            Err(e) => eprintln!("Keeping the current config, failed to reload: {}", e),
+        }
+    }
+}
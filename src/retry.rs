@@ -0,0 +1,71 @@
+use crate::config::MeiliSearchConfig;
+use meilisearch_sdk::errors::{Error, ErrorType};
+use std::future::Future;
+use std::time::Duration;
+
+#[cfg(test)]
+#[path = "tests/retry_tests.rs"]
+mod retry_tests;
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_config(meilisearch_config: &MeiliSearchConfig) -> Self {
+        RetryPolicy {
+            max_retries: meilisearch_config.meilisearch_max_retries,
+            base_delay: Duration::from_millis(meilisearch_config.meilisearch_retry_base_delay_ms),
+            max_delay: Duration::from_millis(meilisearch_config.meilisearch_retry_max_delay_ms),
+        }
+    }
+
+    // Exponential backoff capped at max_delay, with "equal jitter":
+    // half of the delay is fixed and the other half is random
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        let half = exponential / 2;
+        half + half.mul_f64(fastrand::f64())
+    }
+
+    // Run the operation until it succeeds, fails with a non-transient error,
+    // or max_retries is exhausted
+    pub async fn retry<T, F, Fut>(&self, operation: &str, mut f: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match f().await {
+                Ok(result) => return Ok(result),
+                Err(e) if attempt < self.max_retries && is_transient(&e) => {
+                    let delay = self.backoff(attempt);
+                    attempt += 1;
+                    eprintln!(
+                        "{} failed: {}. Retrying in {:?} ({}/{})",
+                        operation, e, delay, attempt, self.max_retries
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+// Errors worth retrying: Meilisearch is unreachable, restarting, or overloaded
+pub fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::HttpError(_) | Error::Timeout => true,
+        Error::MeilisearchCommunication(e) => e.status_code >= 500 || e.status_code == 429,
+        Error::Meilisearch(e) => e.error_type == ErrorType::Internal,
+        _ => false,
+    }
+}
@@ -1,14 +1,105 @@
+use chrono::Utc;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use std::time::Duration;
-use tokio_cron_scheduler::{Job, JobScheduler};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::time::MissedTickBehavior;
+use tokio_cron_scheduler::{Job, JobScheduler, JobSchedulerError};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
 
-use crate::config::{MeiliSearchConfig, ProjectConfig};
-use crate::indexer;
+use crate::checkpoint::ScanCheckpoints;
+use crate::config::{self, Config, MeiliSearchConfig, ProjectConfig};
+use crate::hooks;
+use crate::generations::ScanGenerations;
+use crate::history::ScanHistory;
+use crate::indexer::{self, IndexError, ScanStatus, ScanSummary};
+use crate::outbox::Outbox;
+use crate::retry::RetryPolicy;
+
+#[cfg(test)]
+#[path = "tests/scheduler_tests.rs"]
+mod scheduler_tests;
+
+// Keeps track of running scans so they can be cancelled from the server or on shutdown
+#[derive(Debug)]
+pub struct ScanRegistry {
+    shutdown_token: CancellationToken,
+    running: Mutex<HashMap<String, CancellationToken>>,
+}
+
+impl ScanRegistry {
+    pub fn new(shutdown_token: CancellationToken) -> Self {
+        ScanRegistry {
+            shutdown_token,
+            running: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Register a scan, the returned token is also cancelled on shutdown
+    fn register(&self, project_id: &str) -> CancellationToken {
+        let token = self.shutdown_token.child_token();
+        self.running
+            .lock()
+            .unwrap()
+            .insert(project_id.to_string(), token.clone());
+        token
+    }
+
+    fn unregister(&self, project_id: &str) {
+        self.running.lock().unwrap().remove(project_id);
+    }
+
+    // Cancel the running scan of a project, return false if there is none
+    pub fn cancel(&self, project_id: &str) -> bool {
+        match self.running.lock().unwrap().get(project_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn running_projects(&self) -> Vec<String> {
+        self.running.lock().unwrap().keys().cloned().collect()
+    }
+
+    // Wait until no scan is running, e.g. once they were cancelled on shutdown and
+    // flush their last batch and checkpoint. Returns false if some still run after the timeout.
+    pub async fn wait_until_idle(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while !self.running.lock().unwrap().is_empty() {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(SCAN_DRAIN_POLL_INTERVAL).await;
+        }
+        true
+    }
+}
+
+// how often to check whether the cancelled scans are done on shutdown
+const SCAN_DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// how often to check whether spooled batches can be replayed
+const OUTBOX_REPLAY_INTERVAL: Duration = Duration::from_secs(30);
+
+// A scheduled project, rescheduled when its config or the roots nested in it change
+struct ScheduledProject {
+    job_id: Uuid,
+    project: ProjectConfig,
+    nested_roots: Vec<PathBuf>,
+}
 
 pub async fn schedule_projects(
     projects: &[ProjectConfig], // Use slice instead of &Vec for better ergonomics
     meilisearch_config: &MeiliSearchConfig,
+    data_dir: Option<&Path>,
+    scan_registry: Arc<ScanRegistry>,
+    mut config_updates: watch::Receiver<Config>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     println!("Starting Scheduler!");
     let sched = JobScheduler::new().await?;
@@ -16,44 +107,30 @@ pub async fn schedule_projects(
     // one-job at a time, skip the job if it's already running
     let is_running = Arc::new(AtomicBool::new(false));
 
-    for project in projects {
-        let crontab = project.crontab.clone();
-        // Clone project into an Arc once per iteration
-        let project_arc = Arc::new(project.clone());
-        // Clone meilisearch_config once per iteration
-        let meilisearch_config_arc = Arc::new(meilisearch_config.clone());
-
-        let is_running_clone = is_running.clone();
-        let job = Job::new_async(crontab, move |_uuid, _l| {
-            // Clone Arcs to move into the async block
-            let project = Arc::clone(&project_arc);
-            let meilisearch_config = Arc::clone(&meilisearch_config_arc);
-            let is_running_clone = is_running_clone.clone();
-
-            Box::pin(async move {
-                if is_running_clone.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst) == Ok(false) {
-                    println!("Job started for {}", project.id);
-
-                    let indexer = indexer::Indexer::new(&project, &meilisearch_config);
-
-                    indexer.configure_meilisearch_index().await;
-
-                    match indexer.index_files().await {
-                        Ok((_, files_count)) => {
-                            println!("Indexed {} files in {:?}", files_count, project.root)
-                        }
-                        Err(e) => eprintln!("Error indexing {:?}: {}", project.root, e),
-                    }
-
-                    println!("Job finished for {}", project.id);
-                    is_running_clone.store(false, Ordering::SeqCst);
-                } else {
-                    println!("Another Job is already running! skipping {}", project.id);
-                }
-            })
-        })?;
+    let outbox = data_dir.map(|data_dir| {
+        Outbox::new(data_dir, &meilisearch_config.meilisearch_index_name)
+    });
 
-        sched.add(job).await?;
+    let mut scheduled_projects = HashMap::new();
+    for project in projects {
+        let nested_roots = config::nested_roots(projects, project);
+        let job = project_job(
+            project,
+            &nested_roots,
+            meilisearch_config,
+            data_dir,
+            &scan_registry,
+            &is_running,
+        )?;
+        let job_id = sched.add(job).await?;
+        scheduled_projects.insert(
+            project.id.clone(),
+            ScheduledProject {
+                job_id,
+                project: project.clone(),
+                nested_roots,
+            },
+        );
     }
 
     // Feature 'signal' must be enabled
@@ -61,8 +138,285 @@ pub async fn schedule_projects(
 
     sched.start().await?;
 
-    // Keep the scheduler running
+    // Keep the scheduler running, apply reloaded configs,
+    // and replay spooled batches once Meilisearch is back
+    let retry_policy = RetryPolicy::from_config(meilisearch_config);
+    let mut replay_interval = tokio::time::interval(OUTBOX_REPLAY_INTERVAL);
+    replay_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    replay_interval.tick().await;
+    let mut reloading = true;
     loop {
-        tokio::time::sleep(Duration::from_secs(3600)).await;
+        tokio::select! {
+            _ = replay_interval.tick() => {}
+            changed = config_updates.changed(), if reloading => {
+                if changed.is_err() {
+                    // nobody reloads the config anymore, keep the current schedule
+                    reloading = false;
+                    continue;
+                }
+                let projects = config_updates.borrow_and_update().projects.clone();
+                let reschedule = reschedule_projects(
+                    &sched,
+                    &mut scheduled_projects,
+                    &projects,
+                    meilisearch_config,
+                    data_dir,
+                    &scan_registry,
+                    &is_running,
+                )
+                .await;
+                if let Err(e) = reschedule {
+                    eprintln!("Failed to apply the reloaded config: {}", e);
+                }
+                continue;
+            }
+        }
+        let Some(outbox) = &outbox else {
+            continue;
+        };
+        if outbox.has_pending() && indexer::is_meilisearch_running(meilisearch_config).await {
+            if let Ok(meili_client) = meilisearch_sdk::client::Client::new(
+                &meilisearch_config.meilisearch_url,
+                Some(&meilisearch_config.meilisearch_api_key),
+            ) {
+                let replay = outbox
+                    .replay(&meili_client, &meilisearch_config.meilisearch_index_name, &retry_policy)
+                    .await;
+                if let Err(e) = replay {
+                    eprintln!("Failed to replay outbox: {}", e);
+                }
+            }
+        }
+    }
+}
+
+// Remove, add and reschedule jobs to match the reloaded projects.
+// Running scans of removed or changed projects finish with their old config.
+async fn reschedule_projects(
+    sched: &JobScheduler,
+    scheduled_projects: &mut HashMap<String, ScheduledProject>,
+    projects: &[ProjectConfig],
+    meilisearch_config: &MeiliSearchConfig,
+    data_dir: Option<&Path>,
+    scan_registry: &Arc<ScanRegistry>,
+    is_running: &Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // every project is applied on its own, a failing one keeps its previous job
+    let mut failed_ids = Vec::new();
+    let removed_ids: Vec<String> = scheduled_projects
+        .keys()
+        .filter(|id| !projects.iter().any(|project| &project.id == *id))
+        .cloned()
+        .collect();
+    for project_id in removed_ids {
+        let job_id = scheduled_projects[&project_id].job_id;
+        if let Err(e) = sched.remove(&job_id).await {
+            eprintln!("Reload: failed to remove project {}: {}", project_id, e);
+            failed_ids.push(project_id);
+            continue;
+        }
+        scheduled_projects.remove(&project_id);
+        println!("Reload: removed project {}", project_id);
+    }
+
+    for project in projects {
+        let nested_roots = config::nested_roots(projects, project);
+        let old_job_id = match scheduled_projects.get(&project.id) {
+            Some(scheduled)
+                if scheduled.project == *project && scheduled.nested_roots == nested_roots =>
+            {
+                continue;
+            }
+            Some(scheduled) => Some(scheduled.job_id),
+            None => None,
+        };
+        // the new job is in place before the old one goes, e.g. a mistyped crontab
+        let job = project_job(
+            project,
+            &nested_roots,
+            meilisearch_config,
+            data_dir,
+            scan_registry,
+            is_running,
+        );
+        let job_id = match job {
+            Ok(job) => sched.add(job).await,
+            Err(e) => Err(e),
+        };
+        let job_id = match job_id {
+            Ok(job_id) => job_id,
+            Err(e) => {
+                eprintln!(
+                    "Reload: failed to schedule project {} ({}), keeping its previous schedule: {}",
+                    project.id, project.crontab, e
+                );
+                failed_ids.push(project.id.clone());
+                continue;
+            }
+        };
+        if let Some(old_job_id) = old_job_id {
+            if let Err(e) = sched.remove(&old_job_id).await {
+                eprintln!("Reload: failed to remove the previous job of {}: {}", project.id, e);
+            }
+        }
+        scheduled_projects.insert(
+            project.id.clone(),
+            ScheduledProject {
+                job_id,
+                project: project.clone(),
+                nested_roots,
+            },
+        );
+        let change = if old_job_id.is_some() { "rescheduled" } else { "added" };
+        println!("Reload: {} project {} ({})", change, project.id, project.crontab);
+    }
+
+    if !failed_ids.is_empty() {
+        return Err(format!("projects {} were not applied", failed_ids.join(", ")).into());
+    }
+    Ok(())
+}
+
+fn project_job(
+    project: &ProjectConfig,
+    nested_roots: &[PathBuf],
+    meilisearch_config: &MeiliSearchConfig,
+    data_dir: Option<&Path>,
+    scan_registry: &Arc<ScanRegistry>,
+    is_running: &Arc<AtomicBool>,
+) -> Result<Job, JobSchedulerError> {
+    let crontab = project.crontab.clone();
+    // Clone project into an Arc once per job
+    let project_arc = Arc::new(project.clone());
+    // Clone meilisearch_config once per job
+    let meilisearch_config_arc = Arc::new(meilisearch_config.clone());
+
+    let is_running_clone = is_running.clone();
+    let scan_registry_clone = scan_registry.clone();
+    let data_dir_clone = data_dir.map(Path::to_path_buf);
+    let nested_roots = Arc::new(nested_roots.to_vec());
+    Job::new_async(crontab, move |_uuid, _l| {
+        // Clone Arcs to move into the async block
+        let project = Arc::clone(&project_arc);
+        let meilisearch_config = Arc::clone(&meilisearch_config_arc);
+        let is_running_clone = is_running_clone.clone();
+        let scan_registry = scan_registry_clone.clone();
+        let data_dir = data_dir_clone.clone();
+        let nested_roots = nested_roots.clone();
+
+        Box::pin(async move {
+            if is_running_clone.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst) == Ok(false) {
+                println!("Job started for {}", project.id);
+
+                let summary = run_project_scan(
+                    &project,
+                    &nested_roots,
+                    &meilisearch_config,
+                    data_dir.as_deref(),
+                    &scan_registry,
+                )
+                .await;
+                run_post_scan_hooks(&project, &summary).await;
+                if let Some(data_dir) = &data_dir {
+                    if let Err(e) = ScanHistory::new(data_dir).record(&summary) {
+                        eprintln!("Failed to record scan history: {}", e);
+                    }
+                }
+
+                println!("Job finished for {}", project.id);
+                is_running_clone.store(false, Ordering::SeqCst);
+            } else {
+                println!("Another Job is already running! skipping {}", project.id);
+            }
+        })
+    })
+}
+
+// Configure the index and scan the project, the pre-scan hook may veto the scan
+async fn run_project_scan(
+    project: &ProjectConfig,
+    nested_roots: &[PathBuf],
+    meilisearch_config: &MeiliSearchConfig,
+    data_dir: Option<&Path>,
+    scan_registry: &ScanRegistry,
+) -> ScanSummary {
+    let scan_started = Instant::now();
+    let mut summary = ScanSummary::new(&project.id);
+
+    if let Some(pre_scan_command) = &project.pre_scan_command {
+        if let Err(e) = hooks::run_command_hook("pre_scan", pre_scan_command, &summary).await {
+            eprintln!("Pre-scan hook failed for {}, skipping the scan: {}", project.id, e);
+            summary.errors.push(format!("pre-scan hook failed: {}", e));
+            summary.status = ScanStatus::Failed;
+            summary.duration = scan_started.elapsed();
+            summary.finished_at = Utc::now();
+            return summary;
+        }
+    }
+
+    let mut indexer = indexer::Indexer::new(project, meilisearch_config);
+    indexer.outbox = data_dir.map(|data_dir| {
+        Outbox::new(data_dir, &meilisearch_config.meilisearch_index_name)
+    });
+    indexer.generations = data_dir.map(ScanGenerations::new);
+    indexer.checkpoints = data_dir.map(ScanCheckpoints::new);
+    indexer.nested_roots = nested_roots.to_vec();
+    let roots: Vec<PathBuf> = project.roots().into_iter().map(|root| root.path).collect();
+
+    let configure_result = indexer.configure_meilisearch_index().await;
+    if let Err(e) = &configure_result {
+        eprintln!("Error configuring index for {}: {}", project.id, e);
+        summary.errors.push(e.to_string());
+        summary.status = ScanStatus::Failed;
+    }
+    // with an outbox the scan can go on and spool its batches
+    if configure_result.is_ok() || indexer.outbox.is_some() {
+        let cancel_token = scan_registry.register(&project.id);
+        // a failed or aborted scan still records how far it got
+        let (mut scan_summary, index_error) = match indexer.index_files(&cancel_token).await {
+            Ok((_, scan_summary)) => {
+                println!("Indexed {} files in {:?}", scan_summary.entry_count, roots);
+                (scan_summary, None)
+            }
+            Err((scan_summary, e)) => {
+                eprintln!("Error indexing {:?}: {}", roots, e);
+                (scan_summary, Some(e))
+            }
+        };
+        if scan_summary.walk_error_count > 0 {
+            eprintln!(
+                "{} entries of {:?} couldn't be read: {:?}",
+                scan_summary.walk_error_count, roots, scan_summary.walk_error_kinds
+            );
+        }
+        scan_summary.started_at = summary.started_at;
+        scan_summary.errors.splice(0..0, summary.errors.drain(..));
+        if let Some(e) = index_error {
+            scan_summary.errors.push(e.to_string());
+            scan_summary.status = match e {
+                IndexError::Cancelled | IndexError::TimeLimitExceeded(_) => ScanStatus::Cancelled,
+                _ => ScanStatus::Failed,
+            };
+        }
+        summary = scan_summary;
+        scan_registry.unregister(&project.id);
+    }
+
+    summary.duration = scan_started.elapsed();
+    summary.finished_at = Utc::now();
+    summary
+}
+
+// Hook failures are only logged, the scan itself is already done
+async fn run_post_scan_hooks(project: &ProjectConfig, summary: &ScanSummary) {
+    if let Some(post_scan_command) = &project.post_scan_command {
+        if let Err(e) = hooks::run_command_hook("post_scan", post_scan_command, summary).await {
+            eprintln!("Post-scan hook failed for {}: {}", project.id, e);
+        }
+    }
+    if let Some(webhook_url) = &project.webhook_url {
+        if let Err(e) = hooks::post_webhook("post_scan", webhook_url, summary).await {
+            eprintln!("Webhook failed for {}: {}", project.id, e);
+        }
     }
 }
@@ -1,20 +1,50 @@
-use crate::config::MeiliSearchConfig;
+use crate::config::{ListenAddress, MeiliSearchConfig, ServerConfig};
+use crate::history::ScanHistory;
+use crate::indexer::ScanSummary;
+use crate::scheduler::ScanRegistry;
 
 use axum::{
-    body::Body, extract::{Request, State}, http::uri::Uri, response::{IntoResponse, Response}, routing::any, Router
+    body::Body, extract::{Path, Query, Request, State}, http::{header::AUTHORIZATION, uri::Uri, HeaderMap}, response::{IntoResponse, Redirect, Response}, routing::{any, get, post}, Json, Router
 };
 use hyper::StatusCode;
 use hyper_util::{client::legacy::connect::HttpConnector, rt::TokioExecutor};
-use std::{net::SocketAddr, result::Result};
+use serde::Deserialize;
+use futures::future::{try_join_all, BoxFuture, FutureExt};
+use std::{future::IntoFuture, result::Result, sync::Arc};
 use tower_http::services::ServeDir;
 
+#[cfg(test)]
+#[path = "tests/server_tests.rs"]
+mod server_tests;
+
 type Client = hyper_util::client::legacy::Client<HttpConnector, Body>;
 
 const MEILISEARCH_ENTRY_PREFIX: &str = "/meilisearch";
+const API_ENTRY_PREFIX: &str = "/api";
+
+// State for the management api, requests must carry the meilisearch master key
+#[derive(Clone)]
+struct ApiState {
+    api_key: String,
+    scan_registry: Arc<ScanRegistry>,
+    history: Option<ScanHistory>,
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    project_id: Option<String>,
+    limit: Option<usize>,
+}
 
-pub async fn start_server(meilisearch_config: &MeiliSearchConfig) -> Result<(), Box<dyn std::error::Error>> {
+pub async fn start_server(
+    meilisearch_config: &MeiliSearchConfig,
+    server_config: &ServerConfig,
+    data_dir: Option<&std::path::Path>,
+    scan_registry: Arc<ScanRegistry>,
+) -> Result<(), Box<dyn std::error::Error>> {
     // 在启动服务器前创建index name配置文件
-    let frontend_config_path = "static/meilisearch_config.json";
+    let static_dir = &server_config.static_dir;
+    let frontend_config_path = static_dir.join("meilisearch_config.json");
     let config_content = serde_json::json!({
         "index_name": meilisearch_config.meilisearch_index_name
     });
@@ -24,7 +54,8 @@ pub async fn start_server(meilisearch_config: &MeiliSearchConfig) -> Result<(),
 
     // Serve static files and fallback to index.html for client-side routing
     println!("Starting Server!");
-    let file_server = ServeDir::new("static").not_found_service(ServeDir::new("static/index.html"));
+    let file_server =
+        ServeDir::new(static_dir).not_found_service(ServeDir::new(static_dir.join("index.html")));
 
     // reverse proxy to meilisearch backend for /api requests
     // and host static files otherwise
@@ -33,23 +64,82 @@ pub async fn start_server(meilisearch_config: &MeiliSearchConfig) -> Result<(),
             .build(HttpConnector::new());
     let meilisearch_entry_rule = MEILISEARCH_ENTRY_PREFIX.to_string() + "/{*wildcard}";
     let meilisearch_base_url = meilisearch_config.meilisearch_url.clone();
+    let api_state = ApiState {
+        api_key: meilisearch_config.meilisearch_api_key.clone(),
+        scan_registry,
+        history: data_dir.map(ScanHistory::new),
+    };
+    let api_routes = Router::new()
+        .route("/scans", get(list_scans_handler))
+        .route("/scans/{project_id}/cancel", post(cancel_scan_handler))
+        .route("/history", get(history_handler))
+        .with_state(api_state);
     let routes= Router::new()
         .route(&meilisearch_entry_rule, any(reverse_proxy_handler))
         .with_state((client, meilisearch_base_url))
+        .nest(API_ENTRY_PREFIX, api_routes)
         .fallback_service(file_server);
 
-    serve(routes, 3000).await
+    serve(mount(routes, server_config.base_path()), &server_config.listen).await
 }
 
-async fn serve(app: Router, port: u16) -> Result<(), Box<dyn std::error::Error>> {
-    // Start server
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app)
+// Serve the app below the base path, e.g. behind a reverse proxy at /files/.
+// The frontend resolves its urls relative to the page, so /files redirects to /files/.
+fn mount(app: Router, base_path: &str) -> Router {
+    if base_path.is_empty() {
+        return app;
+    }
+    let index_path = format!("{}/", base_path);
+    Router::new()
+        .route(base_path, get(move || async move { Redirect::permanent(&index_path) }))
+        .nest_service(&format!("{}/", base_path), app)
+}
+
+async fn serve(app: Router, listen: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    // Start server on every listen address
+    let mut servers: Vec<BoxFuture<'static, std::io::Result<()>>> = Vec::new();
+    for address in listen {
+        let address = ListenAddress::parse(address)?;
+        println!("Listening on {}", address);
+        match address {
+            ListenAddress::Tcp(addr) => {
+                let listener = tokio::net::TcpListener::bind(addr).await?;
+                servers.push(axum::serve(listener, app.clone()).into_future().boxed());
+            }
+            #[cfg(unix)]
+            ListenAddress::Unix(path) => {
+                remove_stale_socket(&path)?;
+                let listener = tokio::net::UnixListener::bind(&path)?;
+                servers.push(axum::serve(listener, app.clone()).into_future().boxed());
+            }
+            #[cfg(not(unix))]
+            ListenAddress::Unix(path) => {
+                return Err(format!("Unix sockets are not supported here: {:?}", path).into());
+            }
+        }
+    }
+    try_join_all(servers)
         .await
+        .map(|_| ())
         .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
 }
 
+// A socket left by an earlier run would fail the bind. Anything else at the path is
+// kept, e.g. a file named by a typo in listen.
+#[cfg(unix)]
+pub fn remove_stale_socket(path: &std::path::Path) -> std::io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path),
+        Ok(_) => Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{:?} exists and is not a socket", path),
+        )),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
 
 async fn reverse_proxy_handler(
     State((client, meilisearch_base_url)): State<(Client, String)>,
@@ -76,3 +166,54 @@ async fn reverse_proxy_handler(
         .map_err(|_| StatusCode::BAD_REQUEST)?
         .into_response())
 }
+
+fn is_authorized(headers: &HeaderMap, api_key: &str) -> bool {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| token == api_key)
+}
+
+async fn list_scans_handler(
+    State(api_state): State<ApiState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<String>>, StatusCode> {
+    if !is_authorized(&headers, &api_state.api_key) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(Json(api_state.scan_registry.running_projects()))
+}
+
+async fn cancel_scan_handler(
+    State(api_state): State<ApiState>,
+    Path(project_id): Path<String>,
+    headers: HeaderMap,
+) -> StatusCode {
+    if !is_authorized(&headers, &api_state.api_key) {
+        return StatusCode::UNAUTHORIZED;
+    }
+    if api_state.scan_registry.cancel(&project_id) {
+        println!("Cancelling scan of {} on request", project_id);
+        StatusCode::ACCEPTED
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+async fn history_handler(
+    State(api_state): State<ApiState>,
+    Query(history_query): Query<HistoryQuery>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<ScanSummary>>, StatusCode> {
+    if !is_authorized(&headers, &api_state.api_key) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let Some(history) = &api_state.history else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    history
+        .read(history_query.project_id.as_deref(), history_query.limit.unwrap_or(100))
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
@@ -0,0 +1,157 @@
+use crate::check::{check_config, check_config_content};
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+fn config_with_project(root: &str, project: &str) -> String {
+    format!(
+        r#"[meilisearch]
+meilisearch_url = "http://localhost:7700"
+meilisearch_api_key = "hello_world123456"
+meilisearch_index_name = "filesystem_index"
+
+[[projects]]
+id = "project1"
+root = "{}"
+crontab = "0 0 0 * * *"
+{}
+[server]
+static_dir = "{}"
+"#,
+        root, project, root
+    )
+}
+
+#[test]
+fn test_valid_config() {
+    let dir = tempdir().unwrap();
+    let content = config_with_project(dir.path().to_str().unwrap(), "");
+    assert!(check_config_content(&content, Path::new(".")).is_empty());
+}
+
+#[test]
+fn test_syntax_error_is_located() {
+    let content = "[meilisearch]\nmeilisearch_url = \n";
+    let diagnostics = check_config_content(content, Path::new("."));
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].line, Some(2));
+}
+
+#[test]
+fn test_unknown_keys_are_reported() {
+    let dir = tempdir().unwrap();
+    let content = config_with_project(dir.path().to_str().unwrap(), "index_hiden = true\n")
+        .replace("[meilisearch]\n", "[meilisearch]\nmeiliseach_telemetry = true\n");
+    let diagnostics = check_config_content(&content, Path::new("."));
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].line, Some(2));
+    assert!(diagnostics[0].message.contains("\"meilisearch_telemetry\""));
+    assert_eq!(diagnostics[1].line, Some(11));
+    assert!(diagnostics[1].message.contains("\"index_hidden\""));
+}
+
+#[test]
+fn test_all_problems_are_reported() {
+    let dir = tempdir().unwrap();
+    let missing_root = dir.path().join("missing");
+    let content = config_with_project(
+        dir.path().to_str().unwrap(),
+        &format!(
+            r#"custom_ignore_rule_file = ".meiliignore"
+
+[[projects]]
+id = "project1"
+root = "{}"
+crontab = "every day"
+"#,
+            missing_root.to_str().unwrap()
+        ),
+    );
+    let diagnostics = check_config_content(&content, Path::new("."));
+    let lines: Vec<Option<usize>> = diagnostics.iter().map(|d| d.line).collect();
+    // duplicate id, invalid crontab, missing root, then the warning of the ignore rule file
+    assert_eq!(diagnostics.len(), 4);
+    assert_eq!(lines, vec![Some(13), Some(15), Some(14), Some(10)]);
+    assert!(diagnostics[3].warning);
+    assert!(diagnostics[..3].iter().all(|d| !d.warning));
+}
+
+#[test]
+fn test_short_api_key_is_reported() {
+    let dir = tempdir().unwrap();
+    let content = config_with_project(dir.path().to_str().unwrap(), "")
+        .replace("hello_world123456", "short")
+        .replace("[meilisearch]\n", "[meilisearch]\nmeilisearch_bin_path = \"meilisearch\"\n");
+    let diagnostics = check_config_content(&content, Path::new("."));
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].line, Some(4));
+}
+
+#[test]
+fn test_server_section_is_checked() {
+    let dir = tempdir().unwrap();
+    let content = config_with_project(dir.path().to_str().unwrap(), "")
+        + "listen = [\"[::]:3000\", \"unix:/run/meili.sock\", \"localhost\"]\nbase_path = \"files\"\n";
+    let diagnostics = check_config_content(&content, Path::new("."));
+    assert_eq!(diagnostics.len(), 2);
+    assert!(diagnostics[0].message.contains("\"localhost\""));
+    assert_eq!(diagnostics[0].line, Some(13));
+    assert_eq!(diagnostics[1].line, Some(14));
+}
+
+#[test]
+fn test_every_root_is_checked() {
+    let dir = tempdir().unwrap();
+    let missing_root = dir.path().join("missing");
+    let content = config_with_project(
+        dir.path().to_str().unwrap(),
+        &format!(
+            "roots = [{{ path = \"{}\" }}, {{ path = \"{}\", max_deph = 2 }}]\n",
+            missing_root.to_str().unwrap(),
+            dir.path().to_str().unwrap()
+        ),
+    );
+    let diagnostics = check_config_content(&content, Path::new("."));
+    assert_eq!(diagnostics.len(), 2);
+    assert!(diagnostics[0].message.contains("\"max_depth\""));
+    assert!(diagnostics[1].message.contains("can't be read"));
+    assert_eq!(diagnostics[1].line, Some(10));
+}
+
+#[test]
+fn test_included_files_are_checked() {
+    let dir = tempdir().unwrap();
+    let root = dir.path().to_str().unwrap();
+    fs::create_dir(dir.path().join("conf.d")).unwrap();
+    fs::write(
+        dir.path().join("conf.d").join("photos.toml"),
+        format!(
+            "[[projects]]\nid = \"photos\"\nroot = \"{}\"\ncrontab = \"every day\"\nindex_hiden = true\n",
+            root
+        ),
+    )
+    .unwrap();
+    let content = format!("include = [\"conf.d/*.toml\"]\n{}", config_with_project(root, ""));
+    let diagnostics = check_config_content(&content, dir.path());
+
+    // both problems are located in the included file
+    let included = dir.path().join("conf.d").join("photos.toml");
+    assert_eq!(diagnostics.len(), 2);
+    assert!(diagnostics.iter().all(|d| d.file.as_ref() == Some(&included)));
+    assert!(diagnostics[0].message.contains("\"index_hidden\""));
+    assert_eq!(diagnostics[0].line, Some(5));
+    assert!(diagnostics[1].message.contains("Invalid crontab"));
+    assert_eq!(diagnostics[1].line, Some(4));
+}
+
+#[test]
+fn test_sample_config_is_valid() {
+    // tests run in the crate directory, as do the relative paths of the sample
+    let diagnostics = check_config("doc/config.toml");
+    let problems: Vec<String> = diagnostics
+        .iter()
+        .filter(|d| !d.warning)
+        .map(|d| d.to_string())
+        .collect();
+    assert!(problems.is_empty(), "{:?}", problems);
+}
@@ -0,0 +1,67 @@
+use crate::checkpoint::{ScanCheckpoint, ScanCheckpoints};
+use std::fs;
+use std::path::PathBuf;
+use tempfile::tempdir;
+
+fn checkpoint() -> ScanCheckpoint {
+    ScanCheckpoint {
+        root: PathBuf::from("/data"),
+        roots: Vec::new(),
+        scan_generation: 1,
+        started_at: chrono::Utc::now(),
+        last_path: None,
+        entry_count: 0,
+        bytes_scanned: 0,
+        bytes_allocated: 0,
+        hardlink_duplicate_count: 0,
+        seen_hardlinks: Default::default(),
+        unsaved_hardlinks: Vec::new(),
+        hardlinks_len: 0,
+        indexed_count_before: None,
+    }
+}
+
+#[test]
+fn test_hardlinks_are_appended() {
+    let dir = tempdir().unwrap();
+    let checkpoints = ScanCheckpoints::new(dir.path());
+    let mut checkpoint = checkpoint();
+
+    assert!(checkpoint.see_hardlink("1:10"));
+    assert!(!checkpoint.see_hardlink("1:10"));
+    checkpoints.save("test", &mut checkpoint).unwrap();
+    assert!(checkpoint.see_hardlink("1:11"));
+    checkpoints.save("test", &mut checkpoint).unwrap();
+
+    // each id is written once and the checkpoint itself doesn't hold them
+    let hardlinks = fs::read_to_string(dir.path().join("checkpoints").join("test.hardlinks")).unwrap();
+    assert_eq!(hardlinks, "1:10\n1:11\n");
+    let content = fs::read_to_string(dir.path().join("checkpoints").join("test.json")).unwrap();
+    assert!(!content.contains("1:10"));
+
+    let loaded = checkpoints.load("test").unwrap().unwrap();
+    assert_eq!(loaded.seen_hardlinks, checkpoint.seen_hardlinks);
+
+    checkpoints.clear("test").unwrap();
+    assert!(checkpoints.load("test").unwrap().is_none());
+    assert!(!dir.path().join("checkpoints").join("test.hardlinks").exists());
+}
+
+#[test]
+fn test_hardlinks_of_an_unsaved_checkpoint_are_cut_off() {
+    let dir = tempdir().unwrap();
+    let checkpoints = ScanCheckpoints::new(dir.path());
+    let mut checkpoint = checkpoint();
+    checkpoint.see_hardlink("1:10");
+    checkpoints.save("test", &mut checkpoint).unwrap();
+
+    // a save that appended its ids but never wrote its checkpoint
+    let hardlinks_path = dir.path().join("checkpoints").join("test.hardlinks");
+    fs::write(&hardlinks_path, "1:10\n1:12\n").unwrap();
+
+    let mut loaded = checkpoints.load("test").unwrap().unwrap();
+    assert_eq!(loaded.seen_hardlinks.len(), 1);
+    loaded.see_hardlink("1:13");
+    checkpoints.save("test", &mut loaded).unwrap();
+    assert_eq!(fs::read_to_string(&hardlinks_path).unwrap(), "1:10\n1:13\n");
+}
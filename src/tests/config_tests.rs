@@ -0,0 +1,385 @@
+use crate::config::{
+    expand_string, nested_roots, parse_config as parse_config_content, validate_project_id, Config,
+    OrphanedProjects,
+};
+use std::env;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+const MEILISEARCH_SECTION: &str = r#"
+[meilisearch]
+meilisearch_url = "http://localhost:7700"
+meilisearch_api_key = "hello_world123456"
+meilisearch_index_name = "filesystem_index"
+"#;
+
+fn parse_config(projects: &str) -> Config {
+    parse_config_content(&format!("{}\n{}", MEILISEARCH_SECTION, projects), Path::new(".")).unwrap()
+}
+
+#[test]
+fn test_validate_project_id() {
+    assert!(validate_project_id("project_1-a").is_ok());
+    assert!(validate_project_id("").is_err());
+    assert!(validate_project_id("my project").is_err());
+    assert!(validate_project_id("a\"b").is_err());
+    assert!(validate_project_id("../etc").is_err());
+}
+
+#[test]
+fn test_duplicate_project_ids() {
+    let config = parse_config(
+        r#"
+[[projects]]
+id = "project1"
+root = "./src"
+crontab = "0 0 0 * * *"
+
+[[projects]]
+id = "project1"
+root = "./doc"
+crontab = "0 0 0 * * *"
+"#,
+    );
+
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn test_nested_roots() {
+    let config = parse_config(
+        r#"
+[[projects]]
+id = "data"
+root = "/data"
+crontab = "0 0 0 * * *"
+
+[[projects]]
+id = "photos"
+root = "/data/photos"
+crontab = "0 0 0 * * *"
+
+[[projects]]
+id = "database"
+root = "/database"
+crontab = "0 0 0 * * *"
+"#,
+    );
+
+    let roots = nested_roots(&config.projects, &config.projects[0]);
+    assert_eq!(roots, vec![std::path::PathBuf::from("/data/photos")]);
+    assert!(nested_roots(&config.projects, &config.projects[1]).is_empty());
+    assert!(nested_roots(&config.projects, &config.projects[2]).is_empty());
+}
+
+#[test]
+fn test_previous_ids() {
+    let config = parse_config(
+        r#"
+[[projects]]
+id = "photos"
+root = "/data/photos"
+crontab = "0 0 0 * * *"
+previous_ids = ["pictures"]
+"#,
+    );
+    assert!(config.validate().is_ok());
+    assert_eq!(config.orphaned_projects, OrphanedProjects::Report);
+
+    // an id can't be both in use and a previous one
+    let config = parse_config(
+        r#"
+[[projects]]
+id = "photos"
+root = "/data/photos"
+crontab = "0 0 0 * * *"
+previous_ids = ["pictures"]
+
+[[projects]]
+id = "pictures"
+root = "/data/pictures"
+crontab = "0 0 0 * * *"
+"#,
+    );
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn test_expand_string() {
+    env::set_var("MEILI_FINDER_TEST_DIR", "/srv/meili");
+    let home = env::var("HOME").unwrap();
+
+    assert_eq!(expand_string("$MEILI_FINDER_TEST_DIR/data.ms").unwrap(), "/srv/meili/data.ms");
+    assert_eq!(expand_string("${MEILI_FINDER_TEST_DIR}_old").unwrap(), "/srv/meili_old");
+    assert_eq!(expand_string("~/data.ms").unwrap(), format!("{}/data.ms", home));
+    assert_eq!(expand_string("a~b").unwrap(), "a~b");
+    assert_eq!(expand_string("cost $$5").unwrap(), "cost $5");
+    assert_eq!(expand_string("$(date +%s) $1").unwrap(), "$(date +%s) $1");
+    assert!(expand_string("$MEILI_FINDER_TEST_UNSET").is_err());
+    assert!(expand_string("${MEILI_FINDER_TEST_DIR").is_err());
+}
+
+#[test]
+fn test_config_is_expanded() {
+    env::set_var("MEILI_FINDER_TEST_ROOT", "/data");
+    let config = parse_config_content(
+        &format!(
+            "{}\n{}",
+        MEILISEARCH_SECTION.replace(
+            "[meilisearch]\n",
+            "[meilisearch]\nmeilisearch_db_path = \"$MEILI_FINDER_TEST_ROOT/data.ms\"\n"
+        ),
+        r#"
+[[projects]]
+id = "project1"
+root = "${MEILI_FINDER_TEST_ROOT}/photos"
+crontab = "0 0 0 * * *"
+post_scan_command = "echo $MEILI_FINDER_PROJECT_ID"
+"#
+        ),
+        Path::new("."),
+    )
+    .unwrap();
+
+    assert_eq!(config.meilisearch.meilisearch_db_path, "/data/data.ms");
+    assert_eq!(config.projects[0].root, std::path::PathBuf::from("/data/photos"));
+    // left to the shell running the hook
+    assert_eq!(
+        config.projects[0].post_scan_command.as_deref(),
+        Some("echo $MEILI_FINDER_PROJECT_ID")
+    );
+}
+
+#[test]
+fn test_globs_and_api_key_are_not_expanded() {
+    env::remove_var("RECYCLE");
+    let config = parse_config_content(
+        &format!(
+            "{}\n{}",
+            MEILISEARCH_SECTION.replace("hello_world123456", "hello$world123456"),
+            r#"
+[[projects]]
+id = "project1"
+root = "./src"
+crontab = "0 0 0 * * *"
+include = ["$docs/*.pdf"]
+exclude = ["$RECYCLE.BIN/"]
+"#
+        ),
+        Path::new("."),
+    )
+    .unwrap();
+
+    assert_eq!(config.meilisearch.meilisearch_api_key, "hello$world123456");
+    assert_eq!(config.projects[0].include, vec!["$docs/*.pdf"]);
+    assert_eq!(config.projects[0].exclude, vec!["$RECYCLE.BIN/"]);
+}
+
+#[test]
+fn test_api_key_file_and_environment_overrides() {
+    let dir = tempdir().unwrap();
+    let api_key_file = dir.path().join("master.key");
+    std::fs::write(&api_key_file, "secret_key_from_file\n").unwrap();
+    env::set_var("MEILI_FINDER_MEILISEARCH_INDEX_NAME", "other_index");
+
+    let mut config = parse_config(
+        r#"
+[[projects]]
+id = "project1"
+root = "./src"
+crontab = "0 0 0 * * *"
+"#,
+    );
+    config.meilisearch.meilisearch_api_key_file = Some(api_key_file);
+    config.apply_environment().unwrap();
+    assert_eq!(config.meilisearch.meilisearch_api_key, "secret_key_from_file");
+    assert_eq!(config.meilisearch.meilisearch_index_name, "other_index");
+
+    config.meilisearch.meilisearch_api_key_file = Some(dir.path().join("missing.key"));
+    assert!(config.apply_environment().is_err());
+    env::remove_var("MEILI_FINDER_MEILISEARCH_INDEX_NAME");
+}
+
+#[test]
+fn test_project_defaults_and_includes() {
+    let dir = tempdir().unwrap();
+    fs::create_dir(dir.path().join("conf.d")).unwrap();
+    fs::write(
+        dir.path().join("conf.d").join("b.toml"),
+        "[[projects]]\nid = \"share_b\"\nroot = \"/shares/b\"\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("conf.d").join("a.toml"),
+        "[[projects]]\nid = \"share_a\"\nroot = \"/shares/a\"\nmax_depth = 2\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("conf.d").join("notes.txt"), "not a config").unwrap();
+
+    let content = format!(
+        "include = [\"conf.d/*.toml\"]\n\n[project_defaults]\ncrontab = \"0 0 3 * * *\"\nmax_depth = 5\n{}{}",
+        MEILISEARCH_SECTION,
+        r#"
+[[projects]]
+id = "main"
+root = "/data"
+crontab = "0 0 0 * * *"
+"#
+    );
+    let config = parse_config_content(&content, dir.path()).unwrap();
+
+    // included projects follow in file name order, the project's own keys win
+    let ids: Vec<&str> = config.projects.iter().map(|p| p.id.as_str()).collect();
+    assert_eq!(ids, vec!["main", "share_a", "share_b"]);
+    assert_eq!(config.projects[0].crontab, "0 0 0 * * *");
+    assert_eq!(config.projects[0].max_depth, 5);
+    assert_eq!(config.projects[1].crontab, "0 0 3 * * *");
+    assert_eq!(config.projects[1].max_depth, 2);
+    assert_eq!(config.projects[2].max_depth, 5);
+
+    // only projects can be included
+    fs::write(dir.path().join("conf.d").join("c.toml"), "data_dir = \"/tmp\"\n").unwrap();
+    assert!(parse_config_content(&content, dir.path()).is_err());
+}
+
+#[test]
+fn test_type_errors_keep_their_position() {
+    let content = format!(
+        "{}{}",
+        MEILISEARCH_SECTION,
+        r#"
+[[projects]]
+id = "project1"
+root = "/data"
+crontab = "0 0 0 * * *"
+max_depth = "deep"
+"#
+    );
+    let error = parse_config_content(&content, Path::new(".")).unwrap_err();
+    assert!(error.span().is_some());
+}
+
+#[test]
+fn test_several_roots() {
+    let config = parse_config(
+        r#"
+[[projects]]
+id = "media"
+root = "/mnt/disk1/media"
+roots = [{ path = "/mnt/disk2/media", max_depth = 3 }]
+crontab = "0 0 0 * * *"
+
+[[projects]]
+id = "photos"
+crontab = "0 0 0 * * *"
+
+[[projects.roots]]
+path = "/mnt/disk2/media/photos"
+custom_ignore_rule_file = ".photoignore"
+"#,
+    );
+
+    let roots = config.projects[0].roots();
+    assert_eq!(roots.len(), 2);
+    assert_eq!(roots[0].path, Path::new("/mnt/disk1/media"));
+    assert_eq!(roots[0].max_depth, None);
+    assert_eq!(roots[1].path, Path::new("/mnt/disk2/media"));
+    assert_eq!(roots[1].max_depth, Some(3));
+    let roots = config.projects[1].roots();
+    assert_eq!(roots.len(), 1);
+    assert_eq!(roots[0].custom_ignore_rule_file.as_deref(), Some(".photoignore"));
+    assert!(config.validate().is_ok());
+
+    // nested in the second root of media
+    assert_eq!(
+        nested_roots(&config.projects, &config.projects[0]),
+        vec![std::path::PathBuf::from("/mnt/disk2/media/photos")]
+    );
+}
+
+#[test]
+fn test_project_without_root() {
+    let config = parse_config(
+        r#"
+[[projects]]
+id = "project1"
+crontab = "0 0 0 * * *"
+"#,
+    );
+
+    assert!(config.validate().unwrap_err().contains("no root"));
+}
+
+#[test]
+fn test_invalid_filters() {
+    let config = parse_config(
+        r#"
+[[projects]]
+id = "documents"
+root = "/data"
+crontab = "0 0 0 * * *"
+include = ["*.pdf"]
+exclude = ["{unclosed"]
+min_size = 1000
+max_size = 10
+"#,
+    );
+
+    let problems = config.problems();
+    assert_eq!(problems.len(), 2);
+    assert_eq!(problems[0].key_path, vec!["projects", "0", "exclude"]);
+    assert_eq!(problems[1].key_path, vec!["projects", "0", "min_size"]);
+}
+
+#[test]
+fn test_unknown_ignore_preset() {
+    let config = parse_config(
+        r#"
+[[projects]]
+id = "share"
+root = "/data"
+crontab = "0 0 0 * * *"
+ignore_presets = ["synology", "synolgy"]
+"#,
+    );
+
+    let problems = config.problems();
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].message.contains("\"synolgy\""));
+}
+
+#[test]
+fn test_default_node_id_is_stable() {
+    // document ids must not change with the hostname of a recreated container
+    let config = parse_config(
+        r#"
+[[projects]]
+id = "project1"
+root = "./src"
+crontab = "0 0 0 * * *"
+"#,
+    );
+    assert_eq!(config.meilisearch.meilisearch_node_id, "default");
+}
+
+#[test]
+fn test_mass_deletions_are_refused_by_default() {
+    let config = parse_config(
+        r#"
+[[projects]]
+id = "project1"
+root = "./src"
+crontab = "0 0 0 * * *"
+
+[[projects]]
+id = "project2"
+root = "./src"
+crontab = "0 0 0 * * *"
+allow_mass_delete = true
+"#,
+    );
+    assert_eq!(config.projects[0].max_delete_percentage, Some(50.0));
+    assert!(!config.projects[0].allow_mass_delete);
+    assert!(config.projects[1].allow_mass_delete);
+}
@@ -0,0 +1,120 @@
+use crate::config::ProjectConfig;
+use crate::explain::{explain_ignored, IgnoreReason, Ignored};
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+fn project_config(root: &Path, options: &str) -> ProjectConfig {
+    toml::from_str(&format!(
+        "id = \"project1\"\nroot = {:?}\ncrontab = \"0 0 0 * * *\"\n{}",
+        root, options
+    ))
+    .unwrap()
+}
+
+#[test]
+fn test_gitignore_of_a_folder() {
+    let dir = tempdir().unwrap();
+    let code = dir.path().join("code");
+    fs::create_dir_all(code.join("target").join("debug")).unwrap();
+    fs::write(code.join(".gitignore"), "# build output\ntarget/\n").unwrap();
+    let path = code.join("target").join("debug").join("app");
+    fs::write(&path, "").unwrap();
+
+    // ignored by the folder the walk never enters
+    let project = project_config(dir.path(), "respect_gitignore = true");
+    assert_eq!(
+        explain_ignored(&project, &path).unwrap(),
+        Some(Ignored {
+            path: code.join("target"),
+            reason: IgnoreReason::IgnoreFile {
+                file: code.join(".gitignore"),
+                pattern: "target/".to_string(),
+            },
+        })
+    );
+
+    let project = project_config(dir.path(), "");
+    assert_eq!(explain_ignored(&project, &path).unwrap(), None);
+}
+
+#[test]
+fn test_precedence_of_rules() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join(".ignore"), "*.log\n!keep.log\n").unwrap();
+    fs::write(dir.path().join(".gitignore"), "keep.log\n").unwrap();
+    for name in ["app.log", "keep.log", "notes.txt", ".secret.txt", "report.pdf"] {
+        fs::write(dir.path().join(name), "").unwrap();
+    }
+    let project = project_config(
+        dir.path(),
+        "respect_gitignore = true\nrespect_dot_ignore = true\nexclude = [\"notes.*\"]",
+    );
+    let reason = |name: &str| {
+        explain_ignored(&project, &dir.path().join(name))
+            .unwrap()
+            .map(|ignored| ignored.reason)
+    };
+
+    assert_eq!(
+        reason("app.log"),
+        Some(IgnoreReason::IgnoreFile {
+            file: dir.path().join(".ignore"),
+            pattern: "*.log".to_string(),
+        })
+    );
+    // the whitelist of .ignore wins over .gitignore
+    assert_eq!(reason("keep.log"), None);
+    assert_eq!(reason("notes.txt"), Some(IgnoreReason::ExcludeGlob("notes.*".to_string())));
+    assert_eq!(reason(".secret.txt"), Some(IgnoreReason::Hidden));
+    assert_eq!(reason("report.pdf"), None);
+
+    let project = project_config(dir.path(), "include = [\"*.pdf\"]");
+    assert_eq!(
+        explain_ignored(&project, &dir.path().join("app.log")).unwrap(),
+        Some(Ignored {
+            path: dir.path().join("app.log"),
+            reason: IgnoreReason::NotIncluded,
+        })
+    );
+    // an include glob doesn't bring back hidden files
+    fs::write(dir.path().join("._report.pdf"), "").unwrap();
+    assert_eq!(
+        explain_ignored(&project, &dir.path().join("._report.pdf")).unwrap(),
+        Some(Ignored {
+            path: dir.path().join("._report.pdf"),
+            reason: IgnoreReason::Hidden,
+        })
+    );
+    assert!(explain_ignored(&project, Path::new("/elsewhere")).is_err());
+}
+
+#[test]
+fn test_presets_and_marker_files() {
+    let dir = tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("#recycle").join("old")).unwrap();
+    fs::create_dir(dir.path().join("cache")).unwrap();
+    fs::write(dir.path().join("cache").join(".noindex"), "").unwrap();
+    let project = project_config(
+        dir.path(),
+        "ignore_presets = [\"synology\"]\nexclude_if_present = [\".nomedia\", \".noindex\"]",
+    );
+
+    assert_eq!(
+        explain_ignored(&project, &dir.path().join("#recycle").join("old")).unwrap(),
+        Some(Ignored {
+            path: dir.path().join("#recycle"),
+            reason: IgnoreReason::Preset {
+                preset: "synology".to_string(),
+                glob: "\\#recycle/".to_string(),
+            },
+        })
+    );
+    assert_eq!(
+        explain_ignored(&project, &dir.path().join("cache")).unwrap(),
+        Some(Ignored {
+            path: dir.path().join("cache"),
+            reason: IgnoreReason::MarkerFile(dir.path().join("cache").join(".noindex")),
+        })
+    );
+}
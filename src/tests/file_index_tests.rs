@@ -14,11 +14,17 @@ fn test_filesystem_entry_file() {
         name: "file1.txt".to_string(),
         entry_type: IndexEntryType::File,
         size: Some(1024),
+        allocated_size: None,
         modified_date: Some(Utc::now()),
         is_hidden: false,
         preview: Some("This is a preview".to_string()),
         project_id: "project1".to_string(),
         entry_last_updated: 0,
+        scan_generation: 0,
+        id_scheme: 0,
+        mount_point: None,
+        fs_type: None,
+        hardlink_id: None,
     };
 
     assert_eq!(entry.name, "file1.txt");
@@ -40,11 +46,17 @@ fn test_filesystem_entry_folder() {
         path: "/some/path/my_folder".to_string(),
         entry_type: IndexEntryType::Folder,
         size: None,
+        allocated_size: None,
         modified_date: None,
         is_hidden: false,
         preview: None,
         project_id: "project1".to_string(),
         entry_last_updated: 0,
+        scan_generation: 0,
+        id_scheme: 0,
+        mount_point: None,
+        fs_type: None,
+        hardlink_id: None,
     };
 
     assert_eq!(entry.name, "my_folder");
@@ -65,11 +77,17 @@ fn test_filesystem_entry_hidden() {
         path: "/some/path/.hidden_folder".to_string(),
         entry_type: IndexEntryType::Folder,
         size: None,
+        allocated_size: None,
         modified_date: None,
         is_hidden: true,
         preview: None,
         project_id: "project1".to_string(),
         entry_last_updated: 0,
+        scan_generation: 0,
+        id_scheme: 0,
+        mount_point: None,
+        fs_type: None,
+        hardlink_id: None,
     };
 
     assert_eq!(entry.name, ".hidden_folder");
@@ -0,0 +1,30 @@
+use crate::filter::Filter;
+
+#[test]
+fn test_filter_quotes_strings() {
+    let filter = Filter::eq("project_id", "project1");
+    assert_eq!(filter.to_string(), "project_id = \"project1\"");
+
+    let filter = Filter::eq("project_id", "my \"project\" OR 1 = 1");
+    assert_eq!(filter.to_string(), r#"project_id = "my \"project\" OR 1 = 1""#);
+
+    let filter = Filter::eq("path", "C:\\data\\");
+    assert_eq!(filter.to_string(), r#"path = "C:\\data\\""#);
+}
+
+#[test]
+fn test_filter_groups_expressions() {
+    let filter = Filter::eq("project_id", "project1").and(
+        Filter::lt("scan_generation", 3u64).or(Filter::not_exists("scan_generation")),
+    );
+    assert_eq!(
+        filter.to_string(),
+        "(project_id = \"project1\") AND ((scan_generation < 3) OR (scan_generation NOT EXISTS))"
+    );
+}
+
+#[test]
+fn test_filter_flattens_chains() {
+    let filter = Filter::eq("a", "1").and(Filter::eq("b", "2")).and(Filter::eq("c", "3"));
+    assert_eq!(filter.to_string(), "(a = \"1\") AND (b = \"2\") AND (c = \"3\")");
+}
@@ -0,0 +1,28 @@
+use crate::history::ScanHistory;
+use crate::indexer::{ScanStatus, ScanSummary};
+use std::time::Duration;
+use tempfile::tempdir;
+
+#[test]
+fn test_history_latest_first() {
+    let dir = tempdir().unwrap();
+    let history = ScanHistory::new(dir.path());
+    assert!(history.read(None, 10).unwrap().is_empty());
+
+    for (project_id, entry_count) in [("project1", 1), ("project2", 2), ("project1", 3)] {
+        let mut summary = ScanSummary::new(project_id);
+        summary.entry_count = entry_count;
+        summary.duration = Duration::from_millis(1500);
+        history.record(&summary).unwrap();
+    }
+
+    let summaries = history.read(None, 10).unwrap();
+    assert_eq!(summaries.len(), 3);
+    assert_eq!(summaries[0].entry_count, 3);
+    assert_eq!(summaries[0].duration, Duration::from_millis(1500));
+    assert_eq!(summaries[0].status, ScanStatus::Succeeded);
+
+    let summaries = history.read(Some("project1"), 1).unwrap();
+    assert_eq!(summaries.len(), 1);
+    assert_eq!(summaries[0].entry_count, 3);
+}
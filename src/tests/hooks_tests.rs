@@ -0,0 +1,31 @@
+use crate::hooks::run_command_hook;
+use crate::indexer::ScanSummary;
+use std::fs;
+use tempfile::tempdir;
+
+#[tokio::test]
+async fn test_command_hook_receives_summary() {
+    let dir = tempdir().unwrap();
+    let output_path = dir.path().join("summary.json");
+    let mut summary = ScanSummary::new("project1");
+    summary.entry_count = 42;
+
+    let command = format!("cat > {}", output_path.display());
+    run_command_hook("post_scan", &command, &summary).await.unwrap();
+
+    let payload: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&output_path).unwrap()).unwrap();
+    assert_eq!(payload["event"], "post_scan");
+    assert_eq!(payload["project_id"], "project1");
+    assert_eq!(payload["entry_count"], 42);
+    assert!(payload["errors"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_failing_command_hook() {
+    let summary = ScanSummary::new("project1");
+
+    let result = run_command_hook("pre_scan", "exit 3", &summary).await;
+
+    assert!(result.is_err());
+}
@@ -1,28 +1,65 @@
-use crate::config::{MeiliSearchConfig, ProjectConfig};
-use crate::file_index::IndexEntryType;
-use crate::indexer::Indexer;
+use crate::checkpoint::{ScanCheckpoint, ScanCheckpoints};
+use crate::config::{MeiliSearchConfig, ProjectConfig, RootConfig};
+use crate::file_index::{IndexEntryType, ID_SCHEME};
+use crate::indexer::{exceeds_delete_percentage, IndexError, Indexer};
+use crate::generations::ScanGenerations;
+use crate::outbox::{Outbox, OutboxBatch};
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use tempfile::tempdir;
+use tokio_util::sync::CancellationToken;
 
 fn generate_test_config(rootpath: &Path) -> (MeiliSearchConfig, ProjectConfig) {
     let meilisearch_config = MeiliSearchConfig {
         meilisearch_url: "dummy_url".to_string(),
         meilisearch_api_key: "dummy_key".to_string(),
+        meilisearch_api_key_file: None,
         meilisearch_index_name: "dummy_index".to_string(),
         meilisearch_bin_path: "".to_string(),
         meilisearch_db_path: "".to_string(),
         meilisearch_telemetry: true,
+        meilisearch_max_retries: 0,
+        meilisearch_retry_base_delay_ms: 0,
+        meilisearch_retry_max_delay_ms: 0,
+        meilisearch_node_id: "test_node".to_string(),
+        meilisearch_batch_max_bytes: 20 * 1024 * 1024,
+        meilisearch_gzip: true,
     };
     let project_config = ProjectConfig {
         id: "test".to_string(),
         root: PathBuf::from(rootpath),
+        roots: Vec::new(),
         crontab: "".to_string(),
         index_hidden: true,
         max_depth: 0,
         follow_symlinks: false,
+        same_file_system: false,
+        skip_pseudo_filesystems: true,
         custom_ignore_rule_file: None,
+        respect_gitignore: false,
+        respect_dot_ignore: false,
+        respect_git_global: false,
+        include: Vec::new(),
+        exclude: Vec::new(),
+        ignore_presets: Vec::new(),
+        exclude_if_present: Vec::new(),
+        min_size: None,
+        max_size: None,
+        modified_within: None,
+        max_scan_duration: None,
+        max_entries_per_second: None,
+        pause_when_loadavg_above: None,
+        idle_io_priority: false,
+        required_marker_file: None,
+        max_delete_percentage: None,
+        allow_mass_delete: false,
+        index_walk_errors: false,
+        pre_scan_command: None,
+        post_scan_command: None,
+        webhook_url: None,
+        skip_nested_projects: false,
+        previous_ids: Vec::new(),
     };
     (meilisearch_config, project_config)
 }
@@ -48,17 +85,23 @@ async fn test_index_files_with_files_and_folders() {
     indexer.meili_client = None;
 
     // Perform indexing
-    let (entries, entries_count) = indexer.index_files().await.unwrap();
+    let (entries, summary) = indexer.index_files(&CancellationToken::new()).await.unwrap();
 
     // Validate results
     // 1 child folder, 1 child file and 1 parent
     assert_eq!(entries.len(), 3);
-    assert_eq!(entries_count, 3);
+    assert_eq!(summary.entry_count, 3);
+    assert_eq!(summary.bytes_scanned, file_path.metadata().unwrap().len());
+    assert_eq!(summary.walk_error_count, 0);
 
     let file_entry = entries.iter().find(|e| e.name == "file1.txt").unwrap();
     assert_eq!(file_entry.entry_type, IndexEntryType::File);
     assert_eq!(file_entry.size, Some(file_path.metadata().unwrap().len()));
     assert!(!file_entry.is_hidden);
+    if Path::new("/proc/self/mountinfo").exists() {
+        assert!(file_entry.mount_point.is_some());
+        assert!(file_entry.fs_type.is_some());
+    }
 
     let folder_entry = entries.iter().find(|e| e.name == "folder1").unwrap();
     assert_eq!(folder_entry.entry_type, IndexEntryType::Folder);
@@ -82,14 +125,14 @@ async fn test_index_hidden_file() {
     indexer.meili_client = None;
 
     // Perform indexing
-    let (entries, entries_count) = indexer.index_files().await.unwrap();
+    let (entries, summary) = indexer.index_files(&CancellationToken::new()).await.unwrap();
 
     // Validate the hidden file is indexed
     assert_eq!(entries.len(), 2);
-    assert_eq!(entries_count, 2);
+    assert_eq!(summary.entry_count, 2);
     let hidden_entry = entries.iter().find(|e| e.name == ".hidden_file").unwrap();
     assert_eq!(hidden_entry.entry_type, IndexEntryType::File);
-    assert!(!hidden_entry.size.is_none());
+    assert!(hidden_entry.size.is_some());
     assert!(hidden_entry.is_hidden);
 }
 
@@ -109,12 +152,12 @@ async fn test_index_hidden_folder() {
     indexer.meili_client = None;
 
     // Perform indexing
-    let (entries, entries_count) = indexer.index_files().await.unwrap();
+    let (entries, summary) = indexer.index_files(&CancellationToken::new()).await.unwrap();
 
     // Validate the hidden folder is indexed
     // 1 parent and 1 child hidden folder
     assert_eq!(entries.len(), 2);
-    assert_eq!(entries_count, 2);
+    assert_eq!(summary.entry_count, 2);
     let hidden_entry = entries.iter().find(|e| e.name == ".hidden_folder").unwrap();
     assert_eq!(hidden_entry.entry_type, IndexEntryType::Folder);
     assert!(hidden_entry.is_hidden);
@@ -155,16 +198,20 @@ async fn test_index_files_with_nested_subfolders() {
     indexer.meili_client = None;
 
     // Perform indexing
-    let (entries, entries_count) = indexer.index_files().await.unwrap();
+    let (entries, summary) = indexer.index_files(&CancellationToken::new()).await.unwrap();
 
     // Validate results
     assert_eq!(entries.len(), 6);
-    assert_eq!(entries_count, 6);
+    assert_eq!(summary.entry_count, 6);
 
     let file_entry = entries.iter().find(|e| e.name == "file1.txt").unwrap();
     assert_eq!(file_entry.entry_type, IndexEntryType::File);
     assert_eq!(file_entry.size, Some(file_path.metadata().unwrap().len()));
     assert!(!file_entry.is_hidden);
+    if Path::new("/proc/self/mountinfo").exists() {
+        assert!(file_entry.mount_point.is_some());
+        assert!(file_entry.fs_type.is_some());
+    }
 
     let folder_entry = entries.iter().find(|e| e.name == "folder1").unwrap();
     assert_eq!(folder_entry.entry_type, IndexEntryType::Folder);
@@ -181,3 +228,737 @@ async fn test_index_files_with_nested_subfolders() {
     assert_eq!(file_entry3.size, Some(file_path3.metadata().unwrap().len()));
     assert!(!file_entry3.is_hidden);
 }
+
+#[tokio::test]
+async fn test_cancelled_scan_is_aborted() {
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path();
+    File::create(dir_path.join("file1.txt")).unwrap();
+
+    // Create the Indexer
+    let (meilisaerch_config, project_config) = generate_test_config(dir_path);
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    indexer.meili_client = None;
+
+    // Cancel before the scan starts, no entry should be visited
+    let cancel_token = CancellationToken::new();
+    cancel_token.cancel();
+    let result = indexer.index_files(&cancel_token).await;
+
+    assert!(matches!(result, Err((_, IndexError::Cancelled))));
+}
+
+#[tokio::test]
+async fn test_scan_exceeding_time_limit_is_aborted() {
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path();
+    File::create(dir_path.join("file1.txt")).unwrap();
+
+    // Create the Indexer with a zero time limit
+    let (meilisaerch_config, mut project_config) = generate_test_config(dir_path);
+    project_config.max_scan_duration = Some(0);
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    indexer.meili_client = None;
+
+    let result = indexer.index_files(&CancellationToken::new()).await;
+
+    assert!(matches!(result, Err((_, IndexError::TimeLimitExceeded(_)))));
+}
+
+#[tokio::test]
+async fn test_unreachable_meilisearch_spools_to_outbox() {
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().join("root");
+    fs::create_dir(&dir_path).unwrap();
+    File::create(dir_path.join("file1.txt")).unwrap();
+
+    // Create the Indexer against a port nobody listens on
+    let (mut meilisaerch_config, project_config) = generate_test_config(&dir_path);
+    meilisaerch_config.meilisearch_url = "http://127.0.0.1:1".to_string();
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    let outbox = Outbox::new(dir.path(), &meilisaerch_config.meilisearch_index_name);
+    indexer.outbox = Some(outbox.clone());
+
+    // The scan succeeds and both the batch and the cleanup are spooled in order
+    let (_, summary) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+    assert_eq!(summary.entry_count, 2);
+
+    let pending = outbox.pending().unwrap();
+    assert_eq!(pending.len(), 2);
+    assert!(matches!(&pending[0], OutboxBatch::Upsert { entries } if entries.len() == 2));
+    assert!(matches!(&pending[1], OutboxBatch::Delete { .. }));
+}
+
+#[tokio::test]
+async fn test_unreachable_meilisearch_without_outbox_fails() {
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path();
+    File::create(dir_path.join("file1.txt")).unwrap();
+
+    // Create the Indexer against a port nobody listens on
+    let (mut meilisaerch_config, project_config) = generate_test_config(dir_path);
+    meilisaerch_config.meilisearch_url = "http://127.0.0.1:1".to_string();
+    let indexer = Indexer::new(&project_config, &meilisaerch_config);
+
+    let result = indexer.index_files(&CancellationToken::new()).await;
+
+    assert!(matches!(result, Err((_, IndexError::Meilisearch(_)))));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_walk_errors_are_reported() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path();
+    File::create(dir_path.join("file1.txt")).unwrap();
+
+    // Create a folder whose content can't be listed
+    let locked = dir_path.join("locked");
+    fs::create_dir(&locked).unwrap();
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+    if fs::read_dir(&locked).is_ok() {
+        // root reads it anyway
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        return;
+    }
+
+    // Create the Indexer
+    let (meilisaerch_config, mut project_config) = generate_test_config(dir_path);
+    project_config.index_walk_errors = true;
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    indexer.meili_client = None;
+
+    let result = indexer.index_files(&CancellationToken::new()).await;
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+    let (entries, summary) = result.unwrap();
+
+    // Validate the error is reported and indexed
+    assert_eq!(summary.walk_error_count, 1);
+    assert_eq!(summary.walk_error_kinds.get("PermissionDenied"), Some(&1));
+    assert!(summary.walk_errors[0].path.as_ref().unwrap().ends_with("locked"));
+
+    let error_entry = entries
+        .iter()
+        .find(|e| e.name == "locked" && e.entry_type == IndexEntryType::Error)
+        .unwrap();
+    assert!(error_entry.preview.is_some());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_dangling_symlinks_are_skipped() {
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path();
+    File::create(dir_path.join("file1.txt")).unwrap();
+    std::os::unix::fs::symlink(dir_path.join("missing"), dir_path.join("broken_link")).unwrap();
+
+    // Create the Indexer
+    let (meilisaerch_config, mut project_config) = generate_test_config(dir_path);
+    project_config.index_walk_errors = true;
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    indexer.meili_client = None;
+
+    let (entries, summary) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+
+    // a broken link is neither indexed nor an error
+    assert_eq!(summary.walk_error_count, 0);
+    assert!(entries.iter().all(|e| e.name != "broken_link"));
+    assert!(entries.iter().any(|e| e.name == "file1.txt"));
+}
+
+#[tokio::test]
+async fn test_missing_root_is_refused() {
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().join("not_mounted");
+
+    let (meilisaerch_config, project_config) = generate_test_config(&dir_path);
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    indexer.meili_client = None;
+
+    let result = indexer.index_files(&CancellationToken::new()).await;
+
+    assert!(matches!(result, Err((_, IndexError::RootMissing(_)))));
+}
+
+#[tokio::test]
+async fn test_required_marker_file() {
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path();
+
+    let (meilisaerch_config, mut project_config) = generate_test_config(dir_path);
+    project_config.required_marker_file = Some(".mounted".to_string());
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    indexer.meili_client = None;
+
+    // Without the marker, the volume is considered unmounted
+    let result = indexer.index_files(&CancellationToken::new()).await;
+    assert!(matches!(result, Err((_, IndexError::MarkerMissing(_)))));
+
+    // With the marker, the scan goes on
+    File::create(dir_path.join(".mounted")).unwrap();
+    let (_, summary) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+    assert_eq!(summary.entry_count, 2);
+}
+
+#[test]
+fn test_exceeds_delete_percentage() {
+    assert!(!exceeds_delete_percentage(0, 0, 10.0));
+    assert!(!exceeds_delete_percentage(10, 100, 10.0));
+    assert!(exceeds_delete_percentage(11, 100, 10.0));
+    assert!(exceeds_delete_percentage(100, 100, 99.9));
+}
+
+#[tokio::test]
+async fn test_scan_generations_increase() {
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().join("root");
+    fs::create_dir(&dir_path).unwrap();
+    File::create(dir_path.join("file1.txt")).unwrap();
+
+    // Create the Indexer with local state
+    let (meilisaerch_config, project_config) = generate_test_config(&dir_path);
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    indexer.meili_client = None;
+    indexer.generations = Some(ScanGenerations::new(dir.path()));
+
+    // Every scan gets a new generation, stamped on each entry
+    let (entries, summary) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+    assert_eq!(summary.scan_generation, 1);
+    assert!(entries.iter().all(|e| e.scan_generation == 1));
+
+    let (entries, summary) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+    assert_eq!(summary.scan_generation, 2);
+    assert!(entries.iter().all(|e| e.scan_generation == 2));
+}
+
+#[tokio::test]
+async fn test_scan_resumes_from_checkpoint() {
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().join("root");
+    fs::create_dir(&dir_path).unwrap();
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        File::create(dir_path.join(name)).unwrap();
+    }
+    fs::create_dir(dir_path.join("d")).unwrap();
+    File::create(dir_path.join("d").join("e.txt")).unwrap();
+
+    // Pretend an earlier scan got interrupted after b.txt
+    let checkpoints = ScanCheckpoints::new(dir.path());
+    let mut checkpoint = ScanCheckpoint {
+        root: dir_path.clone(),
+        roots: Vec::new(),
+        scan_generation: 7,
+        started_at: chrono::Utc::now(),
+        last_path: Some(dir_path.join("b.txt")),
+        entry_count: 3,
+        bytes_scanned: 0,
+        bytes_allocated: 0,
+        hardlink_duplicate_count: 0,
+        seen_hardlinks: Default::default(),
+        unsaved_hardlinks: Vec::new(),
+        hardlinks_len: 0,
+        indexed_count_before: None,
+    };
+    checkpoints.save("test", &mut checkpoint).unwrap();
+
+    // Create the Indexer with local state
+    let (meilisaerch_config, project_config) = generate_test_config(&dir_path);
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    indexer.meili_client = None;
+    indexer.checkpoints = Some(checkpoints.clone());
+
+    let (entries, summary) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+
+    // a.txt was done before the interruption, the root is walked again as its ancestor
+    assert!(summary.resumed);
+    assert_eq!(summary.scan_generation, 7);
+    assert!(entries.iter().all(|e| e.name != "a.txt"));
+    for name in ["root", "b.txt", "c.txt", "d", "e.txt"] {
+        assert!(entries.iter().any(|e| e.name == name));
+    }
+    // root, a.txt and b.txt were counted before, c.txt, d and e.txt are new
+    assert_eq!(summary.entry_count, 3 + 3);
+
+    // The completed scan removes its checkpoint
+    assert!(checkpoints.load("test").unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_document_ids_are_scoped_to_the_project() {
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path();
+    File::create(dir_path.join("file1.txt")).unwrap();
+
+    // Two projects over the same root
+    let (meilisaerch_config, mut project_config) = generate_test_config(dir_path);
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    indexer.meili_client = None;
+    project_config.id = "other".to_string();
+    let mut other_indexer = Indexer::new(&project_config, &meilisaerch_config);
+    other_indexer.meili_client = None;
+
+    let (entries, _) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+    let (other_entries, _) = other_indexer.index_files(&CancellationToken::new()).await.unwrap();
+    let file_entry = entries.iter().find(|e| e.name == "file1.txt").unwrap();
+    let other_file_entry = other_entries.iter().find(|e| e.name == "file1.txt").unwrap();
+    assert_ne!(file_entry.uuid, other_file_entry.uuid);
+    assert_eq!(file_entry.id_scheme, ID_SCHEME);
+
+    // The ids are stable across scans
+    let (entries, _) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+    assert!(entries.iter().any(|e| e.uuid == file_entry.uuid));
+}
+
+#[tokio::test]
+async fn test_skip_nested_projects() {
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path();
+    File::create(dir_path.join("file1.txt")).unwrap();
+    fs::create_dir(dir_path.join("photos")).unwrap();
+    File::create(dir_path.join("photos").join("photo1.jpg")).unwrap();
+
+    let (meilisaerch_config, mut project_config) = generate_test_config(dir_path);
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    indexer.meili_client = None;
+    indexer.nested_roots = vec![dir_path.join("photos")];
+
+    // By default the nested root is indexed by the outer project as well
+    let (entries, _) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+    assert!(entries.iter().any(|e| e.name == "photo1.jpg"));
+
+    project_config.skip_nested_projects = true;
+    indexer.project_config = project_config;
+    let (entries, summary) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+    assert!(entries.iter().all(|e| e.name != "photos" && e.name != "photo1.jpg"));
+    assert_eq!(summary.entry_count, 2);
+}
+
+#[tokio::test]
+async fn test_index_files_with_several_roots() {
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let disk1 = dir.path().join("disk1");
+    let disk2 = dir.path().join("disk2");
+    fs::create_dir_all(disk1.join("a").join("b")).unwrap();
+    fs::create_dir_all(disk2.join("c").join("d")).unwrap();
+    File::create(disk1.join("a").join("b").join("deep1.txt")).unwrap();
+    File::create(disk2.join("c").join("d").join("deep2.txt")).unwrap();
+
+    // The second root only goes one level deep
+    let (meilisaerch_config, mut project_config) = generate_test_config(&disk1);
+    project_config.roots = vec![RootConfig {
+        path: disk2.clone(),
+        max_depth: Some(1),
+        custom_ignore_rule_file: None,
+    }];
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    indexer.meili_client = None;
+
+    let (entries, summary) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+
+    for name in ["disk1", "a", "b", "deep1.txt", "disk2", "c"] {
+        assert!(entries.iter().any(|e| e.name == name));
+    }
+    assert!(entries.iter().all(|e| e.name != "d" && e.name != "deep2.txt"));
+    assert!(entries.iter().all(|e| e.project_id == "test"));
+    assert_eq!(summary.entry_count, 6);
+}
+
+#[tokio::test]
+async fn test_scan_resumes_in_a_later_root() {
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let disk1 = dir.path().join("disk1");
+    let disk2 = dir.path().join("disk2");
+    fs::create_dir(&disk1).unwrap();
+    fs::create_dir(&disk2).unwrap();
+    File::create(disk1.join("a.txt")).unwrap();
+    for name in ["b.txt", "c.txt"] {
+        File::create(disk2.join(name)).unwrap();
+    }
+
+    // Pretend an earlier scan got interrupted in the second root after b.txt
+    let checkpoints = ScanCheckpoints::new(dir.path());
+    let mut checkpoint = ScanCheckpoint {
+        root: disk2.clone(),
+        roots: vec![disk1.clone(), disk2.clone()],
+        scan_generation: 3,
+        started_at: chrono::Utc::now(),
+        last_path: Some(disk2.join("b.txt")),
+        entry_count: 4,
+        bytes_scanned: 0,
+        bytes_allocated: 0,
+        hardlink_duplicate_count: 0,
+        seen_hardlinks: Default::default(),
+        unsaved_hardlinks: Vec::new(),
+        hardlinks_len: 0,
+        indexed_count_before: None,
+    };
+    checkpoints.save("test", &mut checkpoint).unwrap();
+
+    let (meilisaerch_config, mut project_config) = generate_test_config(&disk1);
+    project_config.roots = vec![RootConfig {
+        path: disk2.clone(),
+        max_depth: None,
+        custom_ignore_rule_file: None,
+    }];
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    indexer.meili_client = None;
+    indexer.checkpoints = Some(checkpoints.clone());
+
+    let (entries, summary) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+
+    // The first root was done before the interruption
+    assert!(summary.resumed);
+    assert!(entries.iter().all(|e| e.name != "disk1" && e.name != "a.txt"));
+    for name in ["disk2", "b.txt", "c.txt"] {
+        assert!(entries.iter().any(|e| e.name == name));
+    }
+    // only c.txt is new, disk2 and b.txt were counted before
+    assert_eq!(summary.entry_count, 4 + 1);
+}
+
+#[tokio::test]
+async fn test_include_and_exclude_globs() {
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path();
+    for name in ["report.pdf", "letter.docx", "photo.jpg"] {
+        File::create(dir_path.join(name)).unwrap();
+    }
+    fs::create_dir(dir_path.join("drafts")).unwrap();
+    File::create(dir_path.join("drafts").join("draft.pdf")).unwrap();
+    fs::create_dir(dir_path.join("misc")).unwrap();
+
+    let (meilisaerch_config, mut project_config) = generate_test_config(dir_path);
+    project_config.include = vec!["*.pdf".to_string(), "*.docx".to_string()];
+    project_config.exclude = vec!["drafts/".to_string()];
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    indexer.meili_client = None;
+
+    let (entries, _) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+
+    // folders are still walked unless excluded
+    for name in ["report.pdf", "letter.docx", "misc"] {
+        assert!(entries.iter().any(|e| e.name == name));
+    }
+    for name in ["photo.jpg", "drafts", "draft.pdf"] {
+        assert!(entries.iter().all(|e| e.name != name));
+    }
+}
+
+#[tokio::test]
+async fn test_include_globs_dont_index_hidden_files() {
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path();
+    File::create(dir_path.join("report.pdf")).unwrap();
+    // macOS metadata of report.pdf on a share
+    File::create(dir_path.join("._report.pdf")).unwrap();
+    fs::create_dir(dir_path.join(".archive")).unwrap();
+    File::create(dir_path.join(".archive").join("old.pdf")).unwrap();
+
+    let (meilisaerch_config, mut project_config) = generate_test_config(dir_path);
+    project_config.index_hidden = false;
+    project_config.include = vec!["*.pdf".to_string()];
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    indexer.meili_client = None;
+
+    let (entries, _) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+
+    assert!(entries.iter().any(|e| e.name == "report.pdf"));
+    for name in ["._report.pdf", ".archive", "old.pdf"] {
+        assert!(entries.iter().all(|e| e.name != name));
+    }
+}
+
+#[tokio::test]
+async fn test_size_and_age_filters() {
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path();
+    fs::write(dir_path.join("empty.txt"), "").unwrap();
+    fs::write(dir_path.join("small.txt"), "1234").unwrap();
+    fs::write(dir_path.join("large.txt"), "1234567890").unwrap();
+    let mut old_file = File::create(dir_path.join("old.txt")).unwrap();
+    old_file.write_all(b"12345").unwrap();
+    let last_year = std::time::SystemTime::now() - std::time::Duration::from_secs(365 * 86400);
+    old_file.set_modified(last_year).unwrap();
+
+    let (meilisaerch_config, mut project_config) = generate_test_config(dir_path);
+    project_config.min_size = Some(1);
+    project_config.max_size = Some(8);
+    project_config.modified_within = Some(30 * 86400);
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    indexer.meili_client = None;
+
+    let (entries, summary) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+
+    // the root folder and small.txt
+    assert!(entries.iter().any(|e| e.name == "small.txt"));
+    assert_eq!(summary.entry_count, 2);
+}
+
+#[tokio::test]
+async fn test_respect_ignore_files() {
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path();
+    fs::write(dir_path.join(".gitignore"), "target/\n").unwrap();
+    fs::write(dir_path.join(".ignore"), "node_modules/\n").unwrap();
+    fs::create_dir(dir_path.join("target")).unwrap();
+    fs::create_dir(dir_path.join("node_modules")).unwrap();
+    File::create(dir_path.join("main.rs")).unwrap();
+
+    // Ignore files are only honored when asked for
+    let (meilisaerch_config, mut project_config) = generate_test_config(dir_path);
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    indexer.meili_client = None;
+    let (entries, _) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+    assert!(entries.iter().any(|e| e.name == "target"));
+    assert!(entries.iter().any(|e| e.name == "node_modules"));
+
+    project_config.respect_gitignore = true;
+    project_config.respect_dot_ignore = true;
+    indexer.project_config = project_config;
+    let (entries, _) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+    assert!(entries.iter().all(|e| e.name != "target" && e.name != "node_modules"));
+    assert!(entries.iter().any(|e| e.name == "main.rs"));
+}
+
+#[tokio::test]
+async fn test_ignore_presets_and_marker_files() {
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path();
+    fs::create_dir_all(dir_path.join("photos").join("@eaDir")).unwrap();
+    File::create(dir_path.join("photos").join("@eaDir").join("thumb.jpg")).unwrap();
+    File::create(dir_path.join("photos").join("photo.jpg")).unwrap();
+    File::create(dir_path.join("photos").join(".DS_Store")).unwrap();
+    fs::create_dir(dir_path.join("#recycle")).unwrap();
+    fs::create_dir(dir_path.join("cache")).unwrap();
+    File::create(dir_path.join("cache").join(".nomedia")).unwrap();
+    File::create(dir_path.join("cache").join("cached.jpg")).unwrap();
+
+    let (meilisaerch_config, mut project_config) = generate_test_config(dir_path);
+    project_config.ignore_presets = vec!["synology".to_string(), "macos".to_string()];
+    project_config.exclude_if_present = vec![".nomedia".to_string()];
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    indexer.meili_client = None;
+
+    let (entries, summary) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+
+    // the root, photos and photo.jpg
+    assert!(entries.iter().any(|e| e.name == "photo.jpg"));
+    assert_eq!(summary.entry_count, 3);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_hardlinks_are_counted_once() {
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path();
+    fs::create_dir(dir_path.join("snapshot1")).unwrap();
+    fs::create_dir(dir_path.join("snapshot2")).unwrap();
+    fs::write(dir_path.join("snapshot1").join("data.bin"), vec![1u8; 8192]).unwrap();
+    fs::hard_link(
+        dir_path.join("snapshot1").join("data.bin"),
+        dir_path.join("snapshot2").join("data.bin"),
+    )
+    .unwrap();
+    fs::write(dir_path.join("other.bin"), vec![2u8; 100]).unwrap();
+
+    let (meilisaerch_config, project_config) = generate_test_config(dir_path);
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    indexer.meili_client = None;
+
+    let (entries, summary) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+
+    // both links are indexed and can be found by their shared id
+    let links: Vec<_> = entries.iter().filter(|e| e.name == "data.bin").collect();
+    assert_eq!(links.len(), 2);
+    assert!(links[0].hardlink_id.is_some());
+    assert_eq!(links[0].hardlink_id, links[1].hardlink_id);
+    let other = entries.iter().find(|e| e.name == "other.bin").unwrap();
+    assert!(other.hardlink_id.is_none());
+
+    assert_eq!(summary.bytes_scanned, 8192 + 100);
+    assert_eq!(summary.hardlink_duplicate_count, 1);
+    let allocated: u64 = [links[0], other].iter().map(|e| e.allocated_size.unwrap()).sum();
+    assert_eq!(summary.bytes_allocated, allocated);
+}
+
+#[tokio::test]
+async fn test_batches_are_limited_by_payload_size() {
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path();
+    for i in 0..10 {
+        File::create(dir_path.join(format!("file{}.txt", i))).unwrap();
+    }
+
+    let (meilisaerch_config, project_config) = generate_test_config(dir_path);
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    indexer.meili_client = None;
+    // a single entry is larger than that, so every batch holds one entry
+    indexer.batch_max_bytes = 1;
+
+    let (entries, summary) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+
+    // the root folder and 10 files, only the last batch is returned
+    assert_eq!(summary.entry_count, 11);
+    assert_eq!(entries.len(), 1);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_hardlinks_are_counted_once_across_a_resume() {
+    use std::os::unix::fs::MetadataExt;
+
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().join("root");
+    fs::create_dir_all(dir_path.join("a")).unwrap();
+    fs::create_dir_all(dir_path.join("b")).unwrap();
+    fs::write(dir_path.join("a").join("data.bin"), vec![1u8; 8192]).unwrap();
+    fs::hard_link(dir_path.join("a").join("data.bin"), dir_path.join("b").join("data.bin")).unwrap();
+    let metadata = fs::metadata(dir_path.join("a").join("data.bin")).unwrap();
+    let hardlink_id = format!("{}:{}", metadata.dev(), metadata.ino());
+
+    // Pretend an earlier scan got interrupted after the first link
+    let checkpoints = ScanCheckpoints::new(dir.path());
+    let mut checkpoint = ScanCheckpoint {
+        root: dir_path.clone(),
+        roots: Vec::new(),
+        scan_generation: 2,
+        started_at: chrono::Utc::now(),
+        last_path: Some(dir_path.join("a").join("data.bin")),
+        entry_count: 3,
+        bytes_scanned: 8192,
+        bytes_allocated: metadata.blocks() * 512,
+        hardlink_duplicate_count: 0,
+        seen_hardlinks: Default::default(),
+        unsaved_hardlinks: Vec::new(),
+        hardlinks_len: 0,
+        indexed_count_before: None,
+    };
+    checkpoint.see_hardlink(&hardlink_id);
+    checkpoints.save("test", &mut checkpoint).unwrap();
+
+    let (meilisaerch_config, project_config) = generate_test_config(&dir_path);
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    indexer.meili_client = None;
+    indexer.checkpoints = Some(checkpoints);
+
+    let (_, summary) = indexer.index_files(&CancellationToken::new()).await.unwrap();
+
+    // the second link is a duplicate of the inode counted before the interruption
+    assert!(summary.resumed);
+    assert_eq!(summary.entry_count, 5);
+    assert_eq!(summary.bytes_scanned, 8192);
+    assert_eq!(summary.hardlink_duplicate_count, 1);
+}
+
+#[tokio::test]
+async fn test_failed_batches_skip_the_cleanup() {
+    use axum::routing::{get, post};
+    use axum::{http::StatusCode, Json, Router};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    // Create a temporary directory for testing
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().join("root");
+    fs::create_dir(&dir_path).unwrap();
+    File::create(dir_path.join("file1.txt")).unwrap();
+
+    // A Meilisearch that accepts the batch but fails processing it
+    let deleted = Arc::new(AtomicBool::new(false));
+    let delete_called = deleted.clone();
+    let app = Router::new()
+        .route(
+            "/indexes/dummy_index/documents",
+            post(|| async {
+                let task = json!({
+                    "taskUid": 1,
+                    "indexUid": "dummy_index",
+                    "status": "enqueued",
+                    "type": "documentAdditionOrUpdate",
+                    "enqueuedAt": "2024-01-01T00:00:00Z"
+                });
+                (StatusCode::ACCEPTED, Json(task))
+            }),
+        )
+        .route(
+            "/tasks/1",
+            get(|| async {
+                Json(json!({
+                    "uid": 1,
+                    "indexUid": "dummy_index",
+                    "status": "failed",
+                    "type": "documentAdditionOrUpdate",
+                    "details": { "receivedDocuments": 2, "indexedDocuments": 0 },
+                    "error": {
+                        "message": "disk full",
+                        "code": "no_space_left_on_device",
+                        "type": "system",
+                        "link": ""
+                    },
+                    "duration": "PT1S",
+                    "enqueuedAt": "2024-01-01T00:00:00Z",
+                    "startedAt": "2024-01-01T00:00:00Z",
+                    "finishedAt": "2024-01-01T00:00:01Z"
+                }))
+            }),
+        )
+        .route(
+            "/tasks",
+            get(|| async { Json(json!({ "results": [], "total": 1, "limit": 0, "from": null, "next": null })) }),
+        )
+        .route(
+            "/indexes/dummy_index/documents/delete",
+            post(move || async move {
+                delete_called.store(true, Ordering::SeqCst);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    // Create the Indexer with local state
+    let (mut meilisaerch_config, project_config) = generate_test_config(&dir_path);
+    meilisaerch_config.meilisearch_url = format!("http://{}", address);
+    let mut indexer = Indexer::new(&project_config, &meilisaerch_config);
+    let checkpoints = ScanCheckpoints::new(dir.path());
+    indexer.checkpoints = Some(checkpoints.clone());
+
+    let Err((summary, e)) = indexer.index_files(&CancellationToken::new()).await else {
+        panic!("the scan should fail");
+    };
+
+    // the run reports what it scanned, but nothing is deleted and the next run walks
+    // everything again with the same generation
+    assert!(matches!(e, IndexError::TasksFailed(Some(1))));
+    assert_eq!(summary.entry_count, 2);
+    assert_eq!(summary.task_failure_count, Some(1));
+    assert!(!deleted.load(Ordering::SeqCst));
+    let checkpoint = checkpoints.load("test").unwrap().unwrap();
+    assert_eq!(checkpoint.root, dir_path);
+    assert!(checkpoint.last_path.is_none());
+    assert_eq!(checkpoint.entry_count, 0);
+}
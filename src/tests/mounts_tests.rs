@@ -0,0 +1,92 @@
+use crate::mounts::MountTable;
+use std::path::{Path, PathBuf};
+
+const MOUNTINFO: &str = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+23 22 0:21 / /proc rw,nosuid shared:12 - proc proc rw
+24 22 0:22 / /sys rw,nosuid shared:7 - sysfs sysfs rw
+30 22 8:17 / /mnt/disk1 rw,relatime shared:20 - xfs /dev/sdb1 rw
+31 30 0:40 / /mnt/disk1/tmp rw shared:21 - tmpfs tmpfs rw,size=1024k
+32 22 0:41 / /mnt/my\\040share rw,relatime shared:22 - nfs4 nas:/share rw,vers=4.2
+33 30 0:42 / /mnt/disk1/apps rw shared:23 - fuse.lxcfs lxcfs rw,user_id=0
+";
+
+#[test]
+fn test_parse_mountinfo() {
+    let mounts = MountTable::parse(MOUNTINFO);
+
+    let mount = mounts
+        .mount_of(Path::new("/mnt/disk1/media/movie.mkv"))
+        .unwrap();
+    assert_eq!(mount.mount_point, PathBuf::from("/mnt/disk1"));
+    assert_eq!(mount.fs_type, "xfs");
+    // octal escapes of the mount point
+    let mount = mounts.mount_of(Path::new("/mnt/my share/doc.pdf")).unwrap();
+    assert_eq!(mount.fs_type, "nfs4");
+    assert_eq!(mounts.mount_of(Path::new("/home")).unwrap().fs_type, "ext4");
+    assert!(MountTable::parse("").mount_of(Path::new("/")).is_none());
+}
+
+#[test]
+fn test_pseudo_mount_points() {
+    let mounts = MountTable::parse(MOUNTINFO);
+
+    assert_eq!(
+        mounts.for_root(Path::new("/")).pseudo_mount_points(),
+        vec![
+            PathBuf::from("/proc"),
+            PathBuf::from("/sys"),
+            PathBuf::from("/mnt/disk1/tmp"),
+            PathBuf::from("/mnt/disk1/apps")
+        ]
+    );
+    assert_eq!(
+        mounts.for_root(Path::new("/mnt/disk1")).pseudo_mount_points(),
+        vec![
+            PathBuf::from("/mnt/disk1/tmp"),
+            PathBuf::from("/mnt/disk1/apps")
+        ]
+    );
+    // a root on a pseudo filesystem is still scanned
+    assert!(mounts
+        .for_root(Path::new("/mnt/disk1/tmp"))
+        .pseudo_mount_points()
+        .is_empty());
+}
+
+#[test]
+fn test_stacked_mounts() {
+    // an automounted share stays an autofs mount with the share stacked on top
+    let mountinfo = "\
+22 1 8:1 / / rw - ext4 /dev/sda1 rw
+40 22 0:50 / /mnt/nas rw - autofs systemd-1 rw,fd=45
+41 40 0:51 / /mnt/nas rw - nfs4 nas:/export rw,vers=4.2
+42 22 0:52 / /mnt/backup rw - autofs systemd-1 rw,fd=46
+";
+    let mounts = MountTable::parse(mountinfo);
+
+    // the share is scanned, the autofs trigger nobody mounted yet is skipped
+    assert_eq!(
+        mounts.for_root(Path::new("/")).pseudo_mount_points(),
+        vec![PathBuf::from("/mnt/backup")]
+    );
+    assert_eq!(mounts.mount_of(Path::new("/mnt/nas/doc.pdf")).unwrap().fs_type, "nfs4");
+}
+
+#[test]
+fn test_relative_root() {
+    // tests run in the crate directory
+    let src = std::fs::canonicalize("src").unwrap();
+    let mountinfo = format!(
+        "22 1 8:1 / / rw - ext4 /dev/sda1 rw\n23 22 0:21 / {}/tests rw - tmpfs tmpfs rw\n",
+        src.display()
+    );
+    let mounts = MountTable::parse(&mountinfo);
+    let root_mounts = mounts.for_root(Path::new("./src"));
+
+    assert_eq!(root_mounts.pseudo_mount_points(), vec![PathBuf::from("./src/tests")]);
+    let mount = root_mounts.mount_of(Path::new("./src/tests/mounts_tests.rs")).unwrap();
+    assert_eq!(mount.fs_type, "tmpfs");
+    assert_eq!(mount.mount_point, src.join("tests"));
+    assert_eq!(root_mounts.mount_of(Path::new("./src/main.rs")).unwrap().fs_type, "ext4");
+}
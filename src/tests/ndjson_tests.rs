@@ -0,0 +1,38 @@
+use crate::ndjson::NdjsonBatch;
+use std::io::Read;
+
+#[test]
+fn test_plain_batch() {
+    let mut batch = NdjsonBatch::new(false);
+    assert!(batch.is_empty());
+    batch.push(br#"{"id":1}"#).unwrap();
+    batch.push(br#"{"id":2}"#).unwrap();
+    assert_eq!(batch.len(), 18);
+
+    let payload = batch.take().unwrap();
+    assert_eq!(&payload[..], b"{\"id\":1}\n{\"id\":2}\n");
+    // the batch starts over
+    assert!(batch.is_empty());
+    assert!(batch.take().unwrap().is_empty());
+}
+
+#[test]
+fn test_gzip_batch() {
+    let mut batch = NdjsonBatch::new(true);
+    for id in 0..1000 {
+        batch.push(format!(r#"{{"id":{}}}"#, id).as_bytes()).unwrap();
+    }
+    let len = batch.len();
+    let payload = batch.take().unwrap();
+    // the size is counted before compression
+    assert!(payload.len() < len);
+
+    let mut decompressed = String::new();
+    flate2::read::GzDecoder::new(&payload[..])
+        .read_to_string(&mut decompressed)
+        .unwrap();
+    assert_eq!(decompressed.len(), len);
+    assert_eq!(decompressed.lines().nth(42), Some(r#"{"id":42}"#));
+    assert!(batch.is_gzip());
+    assert!(batch.is_empty());
+}
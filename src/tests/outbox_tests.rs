@@ -0,0 +1,65 @@
+use crate::file_index::{FileSystemEntry, IndexEntryType};
+use crate::outbox::{Outbox, OutboxBatch};
+use std::fs::OpenOptions;
+use std::io::Write;
+use tempfile::tempdir;
+
+fn generate_test_entry(path: &str) -> FileSystemEntry {
+    FileSystemEntry {
+        uuid: path.to_string(),
+        path: path.to_string(),
+        name: path.to_string(),
+        entry_type: IndexEntryType::File,
+        size: Some(0),
+        allocated_size: None,
+        modified_date: None,
+        is_hidden: false,
+        preview: None,
+        project_id: "test".to_string(),
+        entry_last_updated: 0,
+        scan_generation: 0,
+        id_scheme: 0,
+        mount_point: None,
+        fs_type: None,
+        hardlink_id: None,
+    }
+}
+
+#[tokio::test]
+async fn test_outbox_keeps_order() {
+    let dir = tempdir().unwrap();
+    let outbox = Outbox::new(dir.path(), "dummy_index");
+    assert!(!outbox.has_pending());
+
+    let upsert = OutboxBatch::Upsert {
+        entries: vec![generate_test_entry("file1.txt")],
+    };
+    let delete = OutboxBatch::Delete {
+        filter: "project_id = test".to_string(),
+    };
+    outbox.append(&upsert).await.unwrap();
+    outbox.append(&delete).await.unwrap();
+
+    assert!(outbox.has_pending());
+    let pending = outbox.pending().unwrap();
+    assert_eq!(pending.len(), 2);
+    assert!(matches!(&pending[0], OutboxBatch::Upsert { entries } if entries[0].path == "file1.txt"));
+    assert!(matches!(&pending[1], OutboxBatch::Delete { filter } if filter == "project_id = test"));
+}
+
+#[tokio::test]
+async fn test_outbox_skips_truncated_entry() {
+    let dir = tempdir().unwrap();
+    let outbox = Outbox::new(dir.path(), "dummy_index");
+    let delete = OutboxBatch::Delete {
+        filter: "project_id = test".to_string(),
+    };
+    outbox.append(&delete).await.unwrap();
+
+    // Simulate a crash in the middle of an append
+    let mut journal = OpenOptions::new().append(true).open(&outbox.path).unwrap();
+    write!(journal, "{{\"operation\":\"upsert\",\"entr").unwrap();
+
+    let pending = outbox.pending().unwrap();
+    assert_eq!(pending.len(), 1);
+}
@@ -0,0 +1,89 @@
+use crate::retry::{is_transient, RetryPolicy};
+use meilisearch_sdk::errors::{Error, MeilisearchCommunicationError};
+use std::cell::Cell;
+use std::time::Duration;
+
+fn generate_test_policy(max_retries: u32, base_delay_ms: u64) -> RetryPolicy {
+    RetryPolicy {
+        max_retries,
+        base_delay: Duration::from_millis(base_delay_ms),
+        max_delay: Duration::from_millis(base_delay_ms * 10),
+    }
+}
+
+fn unavailable_error() -> Error {
+    Error::MeilisearchCommunication(MeilisearchCommunicationError {
+        status_code: 503,
+        message: None,
+        url: "dummy_url".to_string(),
+    })
+}
+
+#[test]
+fn test_backoff_is_exponential_and_capped() {
+    let policy = generate_test_policy(5, 100);
+    for attempt in 0..10 {
+        let expected = Duration::from_millis((100u64 << attempt).min(1000));
+        let delay = policy.backoff(attempt);
+        assert!(delay >= expected / 2);
+        assert!(delay <= expected);
+    }
+}
+
+#[test]
+fn test_transient_errors() {
+    assert!(is_transient(&unavailable_error()));
+    assert!(is_transient(&Error::Timeout));
+    assert!(!is_transient(&Error::InvalidRequest));
+}
+
+#[tokio::test]
+async fn test_retry_until_success() {
+    let policy = generate_test_policy(3, 1);
+    let attempts = Cell::new(0);
+    let result = policy
+        .retry("test", || {
+            attempts.set(attempts.get() + 1);
+            async {
+                if attempts.get() < 3 {
+                    Err(unavailable_error())
+                } else {
+                    Ok(attempts.get())
+                }
+            }
+        })
+        .await;
+
+    assert_eq!(result.unwrap(), 3);
+}
+
+#[tokio::test]
+async fn test_retry_exhausted() {
+    let policy = generate_test_policy(2, 1);
+    let attempts = Cell::new(0);
+    let result: Result<(), Error> = policy
+        .retry("test", || {
+            attempts.set(attempts.get() + 1);
+            async { Err(unavailable_error()) }
+        })
+        .await;
+
+    assert!(result.is_err());
+    // the first attempt plus 2 retries
+    assert_eq!(attempts.get(), 3);
+}
+
+#[tokio::test]
+async fn test_no_retry_on_permanent_error() {
+    let policy = generate_test_policy(5, 1);
+    let attempts = Cell::new(0);
+    let result: Result<(), Error> = policy
+        .retry("test", || {
+            attempts.set(attempts.get() + 1);
+            async { Err(Error::InvalidRequest) }
+        })
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(attempts.get(), 1);
+}
@@ -0,0 +1,197 @@
+use crate::config::Config;
+use crate::scheduler::{reschedule_projects, ScanRegistry};
+use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_cron_scheduler::JobScheduler;
+use tokio_util::sync::CancellationToken;
+
+fn parse_config(projects: &str) -> Config {
+    let meilisearch_section = r#"
+[meilisearch]
+meilisearch_url = "http://localhost:7700"
+meilisearch_api_key = "hello_world123456"
+meilisearch_index_name = "filesystem_index"
+"#;
+    toml::from_str(&format!("{}\n{}", meilisearch_section, projects)).unwrap()
+}
+
+#[tokio::test]
+async fn test_reschedule_projects() {
+    let sched = JobScheduler::new().await.unwrap();
+    let scan_registry = Arc::new(ScanRegistry::new(CancellationToken::new()));
+    let is_running = Arc::new(AtomicBool::new(false));
+    let mut scheduled_projects = HashMap::new();
+
+    let config = parse_config(
+        r#"
+[[projects]]
+id = "project1"
+root = "/data"
+crontab = "0 0 0 * * *"
+
+[[projects]]
+id = "project2"
+root = "/data/photos"
+crontab = "0 0 0 * * *"
+"#,
+    );
+    reschedule_projects(
+        &sched,
+        &mut scheduled_projects,
+        &config.projects,
+        &config.meilisearch,
+        None,
+        &scan_registry,
+        &is_running,
+    )
+    .await
+    .unwrap();
+    assert_eq!(scheduled_projects.len(), 2);
+    let project1_job = scheduled_projects["project1"].job_id;
+    let project2_job = scheduled_projects["project2"].job_id;
+
+    // project1 is unchanged, project2 gets a new schedule and project3 is added
+    let config = parse_config(
+        r#"
+[[projects]]
+id = "project1"
+root = "/data"
+crontab = "0 0 0 * * *"
+
+[[projects]]
+id = "project2"
+root = "/data/photos"
+crontab = "0 0 12 * * *"
+
+[[projects]]
+id = "project3"
+root = "/home"
+crontab = "0 0 0 * * *"
+"#,
+    );
+    reschedule_projects(
+        &sched,
+        &mut scheduled_projects,
+        &config.projects,
+        &config.meilisearch,
+        None,
+        &scan_registry,
+        &is_running,
+    )
+    .await
+    .unwrap();
+    assert_eq!(scheduled_projects.len(), 3);
+    assert_eq!(scheduled_projects["project1"].job_id, project1_job);
+    assert_ne!(scheduled_projects["project2"].job_id, project2_job);
+    assert_eq!(scheduled_projects["project2"].project.crontab, "0 0 12 * * *");
+
+    // removing project2 changes the nested roots of project1, so it is rescheduled too
+    let config = parse_config(
+        r#"
+[[projects]]
+id = "project1"
+root = "/data"
+crontab = "0 0 0 * * *"
+"#,
+    );
+    reschedule_projects(
+        &sched,
+        &mut scheduled_projects,
+        &config.projects,
+        &config.meilisearch,
+        None,
+        &scan_registry,
+        &is_running,
+    )
+    .await
+    .unwrap();
+    assert_eq!(scheduled_projects.len(), 1);
+    assert_ne!(scheduled_projects["project1"].job_id, project1_job);
+    assert!(scheduled_projects["project1"].nested_roots.is_empty());
+}
+
+#[tokio::test]
+async fn test_invalid_crontab_keeps_the_previous_job() {
+    let sched = JobScheduler::new().await.unwrap();
+    let scan_registry = Arc::new(ScanRegistry::new(CancellationToken::new()));
+    let is_running = Arc::new(AtomicBool::new(false));
+    let mut scheduled_projects = HashMap::new();
+
+    let config = parse_config(
+        r#"
+[[projects]]
+id = "project1"
+root = "/data"
+crontab = "0 0 0 * * *"
+
+[[projects]]
+id = "project2"
+root = "/home"
+crontab = "0 0 0 * * *"
+"#,
+    );
+    reschedule_projects(
+        &sched,
+        &mut scheduled_projects,
+        &config.projects,
+        &config.meilisearch,
+        None,
+        &scan_registry,
+        &is_running,
+    )
+    .await
+    .unwrap();
+    let project1_job = scheduled_projects["project1"].job_id;
+
+    // a typo in the crontab of project1 doesn't stop project2 from being applied
+    let config = parse_config(
+        r#"
+[[projects]]
+id = "project1"
+root = "/data"
+crontab = "0 0 0 * *"
+
+[[projects]]
+id = "project2"
+root = "/home"
+crontab = "0 0 12 * * *"
+"#,
+    );
+    let reschedule = reschedule_projects(
+        &sched,
+        &mut scheduled_projects,
+        &config.projects,
+        &config.meilisearch,
+        None,
+        &scan_registry,
+        &is_running,
+    )
+    .await;
+    assert!(reschedule.is_err());
+    assert_eq!(scheduled_projects["project1"].job_id, project1_job);
+    assert_eq!(scheduled_projects["project1"].project.crontab, "0 0 0 * * *");
+    assert_eq!(scheduled_projects["project2"].project.crontab, "0 0 12 * * *");
+}
+
+#[tokio::test]
+async fn test_wait_until_scans_are_done() {
+    let shutdown_token = CancellationToken::new();
+    let scan_registry = Arc::new(ScanRegistry::new(shutdown_token.clone()));
+    assert!(scan_registry.wait_until_idle(Duration::ZERO).await);
+
+    // a scan that takes a moment to stop once cancelled
+    let scan_token = scan_registry.register("project1");
+    let registry = scan_registry.clone();
+    tokio::spawn(async move {
+        scan_token.cancelled().await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        registry.unregister("project1");
+    });
+
+    shutdown_token.cancel();
+    assert!(!scan_registry.wait_until_idle(Duration::from_millis(50)).await);
+    assert!(scan_registry.wait_until_idle(Duration::from_secs(5)).await);
+    assert!(scan_registry.running_projects().is_empty());
+}
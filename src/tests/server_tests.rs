@@ -0,0 +1,64 @@
+use crate::server::mount;
+use axum::{body::Body, http::Request, routing::get, Router};
+use hyper::StatusCode;
+use tower::ServiceExt;
+
+fn test_app() -> Router {
+    Router::new()
+        .route("/api/scans", get(|| async { "scans" }))
+        .fallback(|| async { "index" })
+}
+
+async fn get_path(app: &Router, path: &str) -> (StatusCode, String) {
+    let response = app
+        .clone()
+        .oneshot(Request::get(path).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let location = response
+        .headers()
+        .get("location")
+        .map(|location| location.to_str().unwrap().to_string());
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, location.unwrap_or_else(|| String::from_utf8_lossy(&body).to_string()))
+}
+
+#[tokio::test]
+async fn test_mount_at_root() {
+    let app = mount(test_app(), "");
+    assert_eq!(get_path(&app, "/api/scans").await, (StatusCode::OK, "scans".to_string()));
+    assert_eq!(get_path(&app, "/").await, (StatusCode::OK, "index".to_string()));
+}
+
+#[tokio::test]
+async fn test_mount_below_base_path() {
+    let app = mount(test_app(), "/files");
+    assert_eq!(get_path(&app, "/files/api/scans").await, (StatusCode::OK, "scans".to_string()));
+    assert_eq!(get_path(&app, "/files/").await, (StatusCode::OK, "index".to_string()));
+    assert_eq!(get_path(&app, "/files/search").await, (StatusCode::OK, "index".to_string()));
+    assert_eq!(
+        get_path(&app, "/files").await,
+        (StatusCode::PERMANENT_REDIRECT, "/files/".to_string())
+    );
+    assert_eq!(get_path(&app, "/api/scans").await.0, StatusCode::NOT_FOUND);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_only_stale_sockets_are_removed() {
+    use crate::server::remove_stale_socket;
+
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("meili.sock");
+    drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+    remove_stale_socket(&socket).unwrap();
+    assert!(!socket.exists());
+    // nothing to remove
+    remove_stale_socket(&socket).unwrap();
+
+    let file = dir.path().join("config.toml");
+    std::fs::write(&file, "keep me").unwrap();
+    assert!(remove_stale_socket(&file).is_err());
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "keep me");
+}
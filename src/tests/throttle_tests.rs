@@ -0,0 +1,58 @@
+use crate::throttle::{parse_loadavg, IdleIoPriority, Throttle};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+fn project_config(options: &str) -> crate::config::ProjectConfig {
+    toml::from_str(&format!(
+        "id = \"project1\"\nroot = \"/data\"\ncrontab = \"0 0 0 * * *\"\n{}",
+        options
+    ))
+    .unwrap()
+}
+
+#[test]
+fn test_parse_loadavg() {
+    assert_eq!(parse_loadavg("0.52 0.58 0.59 1/467 12345\n"), Some(0.52));
+    assert_eq!(parse_loadavg(""), None);
+}
+
+#[tokio::test]
+async fn test_max_entries_per_second() {
+    let mut throttle = Throttle::new(&project_config("max_entries_per_second = 200"));
+    let started = Instant::now();
+    for _ in 0..20 {
+        throttle.wait("project1", &CancellationToken::new()).await;
+    }
+    // 20 entries at 5ms each
+    assert!(started.elapsed() >= Duration::from_millis(90));
+
+    // a cancelled scan doesn't wait anymore
+    let mut throttle = Throttle::new(&project_config("max_entries_per_second = 1"));
+    let cancel_token = CancellationToken::new();
+    cancel_token.cancel();
+    let started = Instant::now();
+    for _ in 0..5 {
+        throttle.wait("project1", &cancel_token).await;
+    }
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn test_no_pause_below_the_load_limit() {
+    let mut throttle = Throttle::new(&project_config("pause_when_loadavg_above = 100000.0"));
+    let started = Instant::now();
+    throttle.wait("project1", &CancellationToken::new()).await;
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_idle_io_priority_is_restored() {
+    let previous = super::ioprio::get();
+    {
+        let _outer = IdleIoPriority::enter();
+        let _inner = IdleIoPriority::enter();
+        assert_eq!(super::ioprio::get(), Some(super::ioprio::IDLE));
+    }
+    assert_eq!(super::ioprio::get(), previous);
+}
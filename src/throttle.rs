@@ -0,0 +1,175 @@
+use crate::config::ProjectConfig;
+
+use std::cell::Cell;
+use std::fs;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+#[cfg(test)]
+#[path = "tests/throttle_tests.rs"]
+mod throttle_tests;
+
+const LOADAVG_PATH: &str = "/proc/loadavg";
+// how often the load is read while scanning, and while paused
+const LOADAVG_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+const LOADAVG_PAUSE_INTERVAL: Duration = Duration::from_secs(10);
+// shorter waits are collected, timers are not precise below that anyway
+const MIN_THROTTLE_SLEEP: Duration = Duration::from_millis(10);
+
+// Keeps a scan at max_entries_per_second and pauses it while the system load is too high
+pub struct Throttle {
+    entry_interval: Option<Duration>,
+    max_loadavg: Option<f64>,
+    next_entry_at: Instant,
+    next_load_check: Instant,
+}
+
+impl Throttle {
+    pub fn new(project_config: &ProjectConfig) -> Self {
+        let now = Instant::now();
+        Throttle {
+            entry_interval: project_config
+                .max_entries_per_second
+                .filter(|rate| *rate > 0)
+                .map(|rate| Duration::from_secs(1) / rate),
+            max_loadavg: project_config.pause_when_loadavg_above,
+            next_entry_at: now,
+            next_load_check: now,
+        }
+    }
+
+    // Wait before the next entry as long as needed, returns early once cancelled
+    pub async fn wait(&mut self, project_id: &str, cancel_token: &CancellationToken) {
+        let now = Instant::now();
+        if let Some(max_loadavg) = self.max_loadavg.filter(|_| now >= self.next_load_check) {
+            self.wait_for_load(project_id, max_loadavg, cancel_token).await;
+            self.next_load_check = Instant::now() + LOADAVG_CHECK_INTERVAL;
+        }
+
+        let Some(entry_interval) = self.entry_interval else {
+            return;
+        };
+        // an idle time, e.g. a pause, is not made up for by a burst afterwards
+        let now = Instant::now();
+        self.next_entry_at = self.next_entry_at.max(now) + entry_interval;
+        let ahead = self.next_entry_at - now;
+        if ahead >= MIN_THROTTLE_SLEEP {
+            tokio::select! {
+                _ = tokio::time::sleep(ahead) => {}
+                _ = cancel_token.cancelled() => {}
+            }
+        }
+    }
+
+    async fn wait_for_load(&self, project_id: &str, max_loadavg: f64, cancel_token: &CancellationToken) {
+        let mut paused = false;
+        while let Some(loadavg) = read_loadavg().filter(|loadavg| *loadavg > max_loadavg) {
+            if !paused {
+                println!(
+                    "Pausing scan of {}, load average {} is above {}",
+                    project_id, loadavg, max_loadavg
+                );
+                paused = true;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(LOADAVG_PAUSE_INTERVAL) => {}
+                _ = cancel_token.cancelled() => return,
+            }
+        }
+        if paused {
+            println!("Resuming scan of {}", project_id);
+        }
+    }
+}
+
+// The 1 minute load average, None where /proc/loadavg doesn't exist
+fn read_loadavg() -> Option<f64> {
+    parse_loadavg(&fs::read_to_string(LOADAVG_PATH).ok()?)
+}
+
+fn parse_loadavg(content: &str) -> Option<f64> {
+    content.split_whitespace().next()?.parse().ok()
+}
+
+thread_local! {
+    // scans with an idle IO priority on this thread, and the priority to restore after them
+    static IDLE_SCANS: Cell<(usize, Option<i32>)> = const { Cell::new((0, None)) };
+}
+
+// Lowers the IO priority of the thread to idle while the guard lives, so the disks serve
+// everyone else first. Scans share the thread of the current_thread runtime, the priority
+// is restored once the last of them is done.
+pub struct IdleIoPriority(());
+
+impl IdleIoPriority {
+    pub fn enter() -> Self {
+        IDLE_SCANS.with(|idle_scans| {
+            let (count, mut previous) = idle_scans.get();
+            if count == 0 {
+                previous = ioprio::get();
+                if let Err(e) = ioprio::set(ioprio::IDLE) {
+                    eprintln!("Failed to lower the IO priority: {}", e);
+                }
+            }
+            idle_scans.set((count + 1, previous));
+        });
+        IdleIoPriority(())
+    }
+}
+
+impl Drop for IdleIoPriority {
+    fn drop(&mut self) {
+        IDLE_SCANS.with(|idle_scans| {
+            let (count, previous) = idle_scans.get();
+            if count == 1 {
+                if let Some(previous) = previous {
+                    if let Err(e) = ioprio::set(previous) {
+                        eprintln!("Failed to restore the IO priority: {}", e);
+                    }
+                }
+            }
+            idle_scans.set((count - 1, previous));
+        });
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod ioprio {
+    use std::io;
+
+    // see ioprio_set(2), the priority is the class shifted by 13 bits and the level within it
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_IDLE: i32 = 3;
+    const IOPRIO_CLASS_SHIFT: i32 = 13;
+    pub const IDLE: i32 = IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT;
+
+    // the calling thread, not the whole process
+    pub fn get() -> Option<i32> {
+        let priority = unsafe { libc::syscall(libc::SYS_ioprio_get, IOPRIO_WHO_PROCESS, 0) };
+        (priority >= 0).then_some(priority as i32)
+    }
+
+    pub fn set(priority: i32) -> io::Result<()> {
+        let result =
+            unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, priority) };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod ioprio {
+    use std::io;
+
+    pub const IDLE: i32 = 0;
+
+    pub fn get() -> Option<i32> {
+        None
+    }
+
+    pub fn set(_priority: i32) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "ioprio_set is only available on Linux"))
+    }
+}